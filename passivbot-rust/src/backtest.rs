@@ -0,0 +1,645 @@
+use crate::closes::{calc_closes_long, calc_closes_short};
+use crate::entries::{calc_entries_long, calc_entries_short};
+use crate::types::{
+    Analysis, BacktestParams, BotParamsPair, EMABands, ExchangeParams, ExecutionPolicy, OrderBook,
+    OrderType, Position, StateParams, TrailingPriceBundle, TwapBands,
+};
+use ndarray::{s, Array1, Array2, Array3};
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+pub fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+pub fn std_dev(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let m = mean(values);
+    (values.iter().map(|v| (v - m).powi(2)).sum::<f64>() / (values.len() as f64 - 1.0)).sqrt()
+}
+
+pub fn percentile(values: &[f64], p: f64) -> f64 {
+    let mut sorted: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let rank = (p / 100.0 * (sorted.len() as f64 - 1.0)).round() as usize;
+    sorted[rank.min(sorted.len() - 1)]
+}
+
+/// Minimal seeded xorshift64 generator, used instead of pulling in a `rand` dependency just for
+/// Monte-Carlo fill jitter.
+struct Xorshift64 {
+    state: u64,
+}
+
+impl Xorshift64 {
+    fn new(seed: u64) -> Self {
+        Xorshift64 {
+            state: seed.wrapping_mul(2685821657736338717).wrapping_add(1),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    fn next_unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A multiplicative jitter factor in `[1 - pct, 1 + pct]`.
+    fn jitter_factor(&mut self, pct: f64) -> f64 {
+        1.0 + (self.next_unit() * 2.0 - 1.0) * pct
+    }
+}
+
+/// Distribution of a single analysis metric across Monte-Carlo runs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct McMetric {
+    pub mean: f64,
+    pub std: f64,
+    pub p5: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct McAnalysis {
+    pub adg: McMetric,
+    pub mdg: McMetric,
+    pub sharpe_ratio: McMetric,
+    pub drawdown_worst: McMetric,
+}
+
+/// Re-plays `fills` `mc_runs` times with each fill's price and qty jittered by up to
+/// `±mc_price_jitter_pct`/`±mc_qty_jitter_pct`, analyzing the resulting balance trajectory each
+/// time, and summarizes the distribution of outcomes rather than a single path. `n_timesteps`
+/// must match the candle count the deterministic `Backtest::run` equity curve was built over, so
+/// `equities` here has one entry per candle (forward-filled across candles with no fill) just
+/// like the baseline curve, keeping `adg`/`mdg`/`sharpe_ratio` comparable between the two.
+pub fn run_monte_carlo(
+    fills: &[Fill],
+    starting_balance: f64,
+    mc_runs: usize,
+    mc_price_jitter_pct: f64,
+    mc_qty_jitter_pct: f64,
+    n_timesteps: usize,
+) -> McAnalysis {
+    let mut adg = Vec::with_capacity(mc_runs);
+    let mut mdg = Vec::with_capacity(mc_runs);
+    let mut sharpe_ratio = Vec::with_capacity(mc_runs);
+    let mut drawdown_worst = Vec::with_capacity(mc_runs);
+
+    for seed in 0..mc_runs {
+        let mut rng = Xorshift64::new(seed as u64 + 1);
+        let mut balance = starting_balance;
+        let mut jittered_fills = Vec::with_capacity(fills.len());
+        let mut equities = Vec::with_capacity(n_timesteps);
+        let mut fills_iter = fills.iter().peekable();
+        for k in 0..n_timesteps {
+            while let Some(fill) = fills_iter.peek() {
+                if fill.index != k {
+                    break;
+                }
+                let fill = fills_iter.next().unwrap();
+                let price_factor = rng.jitter_factor(mc_price_jitter_pct);
+                let qty_factor = rng.jitter_factor(mc_qty_jitter_pct);
+                let jittered_pnl = fill.pnl * price_factor * qty_factor;
+                let jittered_fee_paid = fill.fee_paid * price_factor.abs() * qty_factor.abs();
+                balance += jittered_pnl - jittered_fee_paid;
+                jittered_fills.push(Fill {
+                    fill_price: fill.fill_price * price_factor,
+                    fill_qty: fill.fill_qty * qty_factor,
+                    pnl: jittered_pnl,
+                    fee_paid: jittered_fee_paid,
+                    balance,
+                    ..fill.clone()
+                });
+            }
+            equities.push(balance);
+        }
+        let analysis = analyze_backtest(&jittered_fills, &equities);
+        adg.push(analysis.adg);
+        mdg.push(analysis.mdg);
+        sharpe_ratio.push(analysis.sharpe_ratio);
+        drawdown_worst.push(analysis.drawdown_worst);
+    }
+
+    let summarize = |values: &[f64]| McMetric {
+        mean: mean(values),
+        std: std_dev(values),
+        p5: percentile(values, 5.0),
+    };
+    McAnalysis {
+        adg: summarize(&adg),
+        mdg: summarize(&mdg),
+        sharpe_ratio: summarize(&sharpe_ratio),
+        drawdown_worst: summarize(&drawdown_worst),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Fill {
+    pub index: usize,
+    pub symbol: String,
+    pub pnl: f64,
+    pub fee_paid: f64,
+    pub balance: f64,
+    pub fill_qty: f64,
+    pub fill_price: f64,
+    pub position_size: f64,
+    pub position_price: f64,
+    pub order_type: OrderType,
+}
+
+pub struct Backtest {
+    hlcs: Array3<f64>,
+    preferred_coins: Array2<i32>,
+    bot_params_pair: BotParamsPair,
+    exchange_params: Vec<ExchangeParams>,
+    backtest_params: BacktestParams,
+    funding_rates: Array2<f64>,
+    noisiness: Array2<f64>,
+    noisiness_median: Array1<f64>,
+    balance: f64,
+    positions_long: Vec<Position>,
+    positions_short: Vec<Position>,
+    trailing_long: Vec<TrailingPriceBundle>,
+    trailing_short: Vec<TrailingPriceBundle>,
+    /// Candles elapsed since each symbol's long/short position was opened, fed to the
+    /// Dutch-auction close decay. Reset to 0 whenever the position is flat.
+    candles_since_open_long: Vec<usize>,
+    candles_since_open_short: Vec<usize>,
+}
+
+impl Backtest {
+    pub fn new(
+        hlcs: Array3<f64>,
+        preferred_coins: Array2<i32>,
+        bot_params_pair: BotParamsPair,
+        exchange_params: Vec<ExchangeParams>,
+        backtest_params: &BacktestParams,
+        funding_rates: Array2<f64>,
+    ) -> Self {
+        let n_symbols = hlcs.shape()[1];
+        let noisiness_window = bot_params_pair.long.entry_qty_vol_window.max(1);
+        let noisiness = calc_noisiness(&hlcs, noisiness_window);
+        let noisiness_median = Array1::from_shape_fn(n_symbols, |idx| {
+            median(noisiness.column(idx).to_vec().as_slice())
+        });
+        Backtest {
+            hlcs,
+            preferred_coins,
+            bot_params_pair,
+            exchange_params,
+            backtest_params: backtest_params.clone(),
+            funding_rates,
+            noisiness,
+            noisiness_median,
+            balance: backtest_params.starting_balance,
+            positions_long: vec![Position::default(); n_symbols],
+            positions_short: vec![Position::default(); n_symbols],
+            trailing_long: vec![TrailingPriceBundle::default(); n_symbols],
+            trailing_short: vec![TrailingPriceBundle::default(); n_symbols],
+            candles_since_open_long: vec![0; n_symbols],
+            candles_since_open_short: vec![0; n_symbols],
+        }
+    }
+
+    pub fn run(&mut self) -> (Vec<Fill>, Vec<f64>) {
+        let n_timesteps = self.hlcs.shape()[0];
+        let n_symbols = self.hlcs.shape()[1];
+        let mut fills = Vec::new();
+        let mut equities = Vec::with_capacity(n_timesteps);
+
+        for k in 0..n_timesteps {
+            for idx in 0..n_symbols {
+                let high = self.hlcs[[k, idx, 0]];
+                let low = self.hlcs[[k, idx, 1]];
+                let close = self.hlcs[[k, idx, 2]];
+                let symbol = self
+                    .backtest_params
+                    .symbols
+                    .get(idx)
+                    .cloned()
+                    .unwrap_or_default();
+                let exchange_params = self.exchange_params[idx];
+                let position_long = self.positions_long[idx];
+                let position_short = self.positions_short[idx];
+
+                let state_params = StateParams {
+                    balance: self.balance,
+                    order_book: OrderBook {
+                        bid: close,
+                        ask: close,
+                    },
+                    ema_bands: EMABands::default(),
+                    twap_bands: TwapBands::default(),
+                    noisiness_now: self.noisiness[[k, idx]],
+                    noisiness_median: self.noisiness_median[idx],
+                };
+
+                // Funding accrues every `funding_interval` candles on whichever side is open,
+                // charged against the mark price (here, the candle close).
+                let funding_interval = self.backtest_params.funding_interval;
+                if funding_interval > 0 && k > 0 && k % funding_interval == 0 {
+                    let funding_rate = self.funding_rates[[k, idx]];
+                    if position_long.size != 0.0 {
+                        let funding_payment =
+                            position_long.size * close * exchange_params.c_mult * funding_rate;
+                        self.balance -= funding_payment;
+                        fills.push(Fill {
+                            index: k,
+                            symbol: symbol.clone(),
+                            pnl: -funding_payment,
+                            fee_paid: 0.0,
+                            balance: self.balance,
+                            fill_qty: position_long.size,
+                            fill_price: close,
+                            position_size: position_long.size,
+                            position_price: position_long.price,
+                            order_type: OrderType::Funding,
+                        });
+                    }
+                    if position_short.size != 0.0 {
+                        let funding_payment =
+                            position_short.size * close * exchange_params.c_mult * funding_rate;
+                        self.balance -= funding_payment;
+                        fills.push(Fill {
+                            index: k,
+                            symbol: symbol.clone(),
+                            pnl: -funding_payment,
+                            fee_paid: 0.0,
+                            balance: self.balance,
+                            fill_qty: position_short.size,
+                            fill_price: close,
+                            position_size: position_short.size,
+                            position_price: position_short.price,
+                            order_type: OrderType::Funding,
+                        });
+                    }
+                }
+
+                // long side, bracket mode only: the take-profit and stop legs are OCA-linked, so
+                // at most one of them fills, booked as soon as the candle's high/low crosses it.
+                // Outside bracket mode the stop leg is left to the unconditional closes_long pass
+                // below, which already runs calc_stop_close_long first in priority order — adding
+                // a second manual check here would double-fire the same stop within one candle.
+                if self.bot_params_pair.long.close_bracket_mode && position_long.size > 0.0 {
+                    let (tp_order, _) = crate::closes::calc_bracket_close_long(
+                        &exchange_params,
+                        &state_params,
+                        &self.bot_params_pair.long,
+                        &position_long,
+                    );
+                    // the stop leg triggers on the candle's low, not only on its close
+                    let stop_state = StateParams {
+                        order_book: OrderBook { bid: high, ask: low },
+                        ..state_params
+                    };
+                    let stop_order = crate::closes::calc_stop_close_long(
+                        &exchange_params,
+                        &stop_state,
+                        &self.bot_params_pair.long,
+                        &position_long,
+                    );
+                    let tp_hit = tp_order.qty != 0.0 && high >= tp_order.price;
+                    let stop_hit = stop_order.qty != 0.0 && low <= stop_order.price;
+                    let chosen = match (tp_hit, stop_hit) {
+                        (true, true) => {
+                            if (tp_order.price - close).abs() <= (stop_order.price - close).abs() {
+                                Some(tp_order)
+                            } else {
+                                Some(stop_order)
+                            }
+                        }
+                        (true, false) => Some(tp_order),
+                        (false, true) => Some(stop_order),
+                        (false, false) => None,
+                    };
+                    if let Some(order) = chosen {
+                        self.apply_fill(idx, &symbol, k, order, &mut fills, true);
+                    }
+                }
+
+                let position_long = self.positions_long[idx];
+                let entries_long = calc_entries_long(
+                    &exchange_params,
+                    &state_params,
+                    &self.bot_params_pair.long,
+                    &position_long,
+                    &self.trailing_long[idx],
+                );
+                for order in entries_long {
+                    if order.qty > 0.0 && order.price >= low && order.price <= high {
+                        self.apply_fill(idx, &symbol, k, order, &mut fills, true);
+                    }
+                }
+
+                if !self.bot_params_pair.long.close_bracket_mode {
+                    let position_long = self.positions_long[idx];
+                    let closes_long = calc_closes_long(
+                        &exchange_params,
+                        &state_params,
+                        &self.bot_params_pair.long,
+                        &position_long,
+                        &self.trailing_long[idx],
+                        self.candles_since_open_long[idx],
+                    );
+                    for order in closes_long {
+                        if order.qty < 0.0 && order.price >= low && order.price <= high {
+                            self.apply_fill(idx, &symbol, k, order, &mut fills, true);
+                        }
+                    }
+                }
+
+                // short side, bracket mode only: mirrors the long bracket check above against the
+                // candle's high. Outside bracket mode the stop leg is left to the unconditional
+                // closes_short pass below for the same reason as the long side.
+                let position_short = self.positions_short[idx];
+                if self.bot_params_pair.short.close_bracket_mode && position_short.size < 0.0 {
+                    let (tp_order, _) = crate::closes::calc_bracket_close_short(
+                        &exchange_params,
+                        &state_params,
+                        &self.bot_params_pair.short,
+                        &position_short,
+                    );
+                    // the stop leg triggers on the candle's high, not only on its close
+                    let stop_state = StateParams {
+                        order_book: OrderBook { bid: high, ask: low },
+                        ..state_params
+                    };
+                    let stop_order = crate::closes::calc_stop_close_short(
+                        &exchange_params,
+                        &stop_state,
+                        &self.bot_params_pair.short,
+                        &position_short,
+                    );
+                    let tp_hit = tp_order.qty != 0.0 && low <= tp_order.price;
+                    let stop_hit = stop_order.qty != 0.0 && high >= stop_order.price;
+                    let chosen = match (tp_hit, stop_hit) {
+                        (true, true) => {
+                            if (tp_order.price - close).abs() <= (stop_order.price - close).abs() {
+                                Some(tp_order)
+                            } else {
+                                Some(stop_order)
+                            }
+                        }
+                        (true, false) => Some(tp_order),
+                        (false, true) => Some(stop_order),
+                        (false, false) => None,
+                    };
+                    if let Some(order) = chosen {
+                        self.apply_fill(idx, &symbol, k, order, &mut fills, false);
+                    }
+                }
+
+                let position_short = self.positions_short[idx];
+                let entries_short = calc_entries_short(
+                    &exchange_params,
+                    &state_params,
+                    &self.bot_params_pair.short,
+                    &position_short,
+                    &self.trailing_short[idx],
+                );
+                for order in entries_short {
+                    if order.qty < 0.0 && order.price >= low && order.price <= high {
+                        self.apply_fill(idx, &symbol, k, order, &mut fills, false);
+                    }
+                }
+
+                if !self.bot_params_pair.short.close_bracket_mode {
+                    let position_short = self.positions_short[idx];
+                    let closes_short = calc_closes_short(
+                        &exchange_params,
+                        &state_params,
+                        &self.bot_params_pair.short,
+                        &position_short,
+                        &self.trailing_short[idx],
+                        self.candles_since_open_short[idx],
+                    );
+                    for order in closes_short {
+                        if order.qty > 0.0 && order.price >= low && order.price <= high {
+                            self.apply_fill(idx, &symbol, k, order, &mut fills, false);
+                        }
+                    }
+                }
+
+                self.candles_since_open_long[idx] = if self.positions_long[idx].size != 0.0 {
+                    self.candles_since_open_long[idx] + 1
+                } else {
+                    0
+                };
+                self.candles_since_open_short[idx] = if self.positions_short[idx].size != 0.0 {
+                    self.candles_since_open_short[idx] + 1
+                } else {
+                    0
+                };
+            }
+            equities.push(self.balance);
+        }
+        (fills, equities)
+    }
+
+    fn apply_fill(
+        &mut self,
+        idx: usize,
+        symbol: &str,
+        k: usize,
+        order: crate::types::Order,
+        fills: &mut Vec<Fill>,
+        is_long: bool,
+    ) {
+        let exchange_params = self.exchange_params[idx];
+        let position = if is_long {
+            &mut self.positions_long[idx]
+        } else {
+            &mut self.positions_short[idx]
+        };
+        let is_close = (is_long && order.qty < 0.0) || (!is_long && order.qty > 0.0);
+        let bot_params = if is_long {
+            &self.bot_params_pair.long
+        } else {
+            &self.bot_params_pair.short
+        };
+        let policy = if is_close {
+            bot_params.close_execution_policy
+        } else {
+            bot_params.entry_execution_policy
+        };
+        let crosses_as_ioc =
+            order.order_type.is_grid_order() && policy == ExecutionPolicy::ImmediateOrCancel;
+        let fee_rate = if order.order_type.is_taker() || crosses_as_ioc {
+            self.backtest_params.taker_fee
+        } else {
+            self.backtest_params.maker_fee
+        };
+        let fee_paid = order.qty.abs() * order.price * exchange_params.c_mult * fee_rate;
+        let mut pnl = 0.0;
+        if is_close {
+            pnl = if is_long {
+                -order.qty * (order.price - position.price) * exchange_params.c_mult
+            } else {
+                -order.qty * (order.price - position.price) * exchange_params.c_mult
+            };
+        }
+        let new_size = position.size + order.qty;
+        if !is_close || new_size == 0.0 {
+            position.price = if new_size != 0.0 {
+                (position.size * position.price + order.qty * order.price) / new_size
+            } else {
+                0.0
+            };
+        }
+        position.size = new_size;
+        self.balance += pnl - fee_paid;
+        fills.push(Fill {
+            index: k,
+            symbol: symbol.to_string(),
+            pnl,
+            fee_paid,
+            balance: self.balance,
+            fill_qty: order.qty,
+            fill_price: order.price,
+            position_size: position.size,
+            position_price: position.price,
+            order_type: order.order_type,
+        });
+    }
+}
+
+pub fn calc_noisiness(hlcs: &Array3<f64>, window: usize) -> Array2<f64> {
+    let n_timesteps = hlcs.shape()[0];
+    let n_symbols = hlcs.shape()[1];
+    let mut noisiness = Array2::zeros((n_timesteps, n_symbols));
+    for idx in 0..n_symbols {
+        for k in 0..n_timesteps {
+            let start = k.saturating_sub(window.saturating_sub(1));
+            let mut sum_range = 0.0;
+            let mut count = 0.0;
+            for t in start..=k {
+                let high = hlcs[[t, idx, 0]];
+                let low = hlcs[[t, idx, 1]];
+                let close = hlcs[[t, idx, 2]];
+                if close > 0.0 {
+                    sum_range += (high - low) / close;
+                }
+                count += 1.0;
+            }
+            noisiness[[k, idx]] = if count > 0.0 { sum_range / count } else { 0.0 };
+        }
+    }
+    noisiness
+}
+
+pub fn calc_volumes(hlcvs: &Array3<f64>, window: usize) -> Array2<f64> {
+    let n_timesteps = hlcvs.shape()[0];
+    let n_symbols = hlcvs.shape()[1];
+    let mut volumes = Array2::zeros((n_timesteps, n_symbols));
+    for idx in 0..n_symbols {
+        for k in 0..n_timesteps {
+            let start = k.saturating_sub(window.saturating_sub(1));
+            let slice = hlcvs.slice(s![start..=k, idx, 3]);
+            volumes[[k, idx]] = slice.sum();
+        }
+    }
+    volumes
+}
+
+pub fn analyze_backtest(fills: &[Fill], equities: &[f64]) -> Analysis {
+    if equities.is_empty() {
+        return Analysis::default();
+    }
+    let n = equities.len() as f64;
+    let starting = equities[0].max(1e-12);
+    let ending = *equities.last().unwrap();
+    let adg = (ending / starting).powf(1.0 / n) - 1.0;
+    let mdg = adg;
+
+    let mut returns = Vec::with_capacity(equities.len().saturating_sub(1));
+    for w in equities.windows(2) {
+        if w[0] != 0.0 {
+            returns.push((w[1] - w[0]) / w[0]);
+        }
+    }
+    let mean_return = if returns.is_empty() {
+        0.0
+    } else {
+        returns.iter().sum::<f64>() / returns.len() as f64
+    };
+    let std_return = if returns.len() < 2 {
+        0.0
+    } else {
+        (returns
+            .iter()
+            .map(|r| (r - mean_return).powi(2))
+            .sum::<f64>()
+            / (returns.len() as f64 - 1.0))
+            .sqrt()
+    };
+    let sharpe_ratio = if std_return > 0.0 {
+        mean_return / std_return
+    } else {
+        0.0
+    };
+
+    let mut peak = equities[0];
+    let mut worst_drawdown = 0.0_f64;
+    for &eq in equities {
+        if eq > peak {
+            peak = eq;
+        }
+        if peak > 0.0 {
+            let drawdown = (peak - eq) / peak;
+            if drawdown > worst_drawdown {
+                worst_drawdown = drawdown;
+            }
+        }
+    }
+
+    let (mut profit, mut loss) = (0.0, 0.0);
+    let mut total_funding_paid = 0.0;
+    for fill in fills {
+        if fill.order_type == OrderType::Funding {
+            total_funding_paid += -fill.pnl;
+            continue;
+        }
+        if fill.pnl > 0.0 {
+            profit += fill.pnl;
+        } else {
+            loss += -fill.pnl;
+        }
+    }
+    let loss_profit_ratio = if profit > 0.0 { loss / profit } else { 0.0 };
+
+    Analysis {
+        adg,
+        mdg,
+        sharpe_ratio,
+        drawdown_worst: worst_drawdown,
+        equity_balance_diff_mean: 0.0,
+        equity_balance_diff_max: 0.0,
+        loss_profit_ratio,
+        total_funding_paid,
+    }
+}
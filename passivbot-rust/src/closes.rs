@@ -0,0 +1,750 @@
+use crate::types::{
+    BotParams, ExchangeParams, ExecutionPolicy, Order, OrderType, Position, StateParams,
+    TrailingPriceBundle,
+};
+
+fn round_to_step(value: f64, step: f64) -> f64 {
+    if step <= 0.0 {
+        return value;
+    }
+    (value / step).round() * step
+}
+
+/// Applies `policy` to a sell-side (long close) grid price: `PostOnly` clamps it above the best
+/// bid so it always rests as maker, `ImmediateOrCancel` forces it to the bid so it fills
+/// immediately as taker, `Limit` leaves it untouched.
+fn apply_execution_policy_sell(price: f64, bid: f64, price_step: f64, policy: ExecutionPolicy) -> f64 {
+    if bid <= 0.0 {
+        return price;
+    }
+    match policy {
+        ExecutionPolicy::Limit => price,
+        ExecutionPolicy::PostOnly => price.max(round_to_step(bid + price_step, price_step)),
+        ExecutionPolicy::ImmediateOrCancel => round_to_step(bid, price_step),
+    }
+}
+
+/// Mirror of [`apply_execution_policy_sell`] for buy-side (short close) grid prices.
+fn apply_execution_policy_buy(price: f64, ask: f64, price_step: f64, policy: ExecutionPolicy) -> f64 {
+    if ask <= 0.0 {
+        return price;
+    }
+    match policy {
+        ExecutionPolicy::Limit => price,
+        ExecutionPolicy::PostOnly => price.min(round_to_step(ask - price_step, price_step)),
+        ExecutionPolicy::ImmediateOrCancel => round_to_step(ask, price_step),
+    }
+}
+
+/// Round-trip fee cost (as a fraction of position price) of entering and then closing through
+/// the grid, each leg priced as maker or taker depending on its configured execution policy.
+/// Negative where a leg earns a maker rebate.
+fn round_trip_fee_frac(exchange_params: &ExchangeParams, bot_params: &BotParams) -> f64 {
+    let entry_fee = if bot_params.entry_execution_policy == ExecutionPolicy::ImmediateOrCancel {
+        exchange_params.taker_fee
+    } else {
+        exchange_params.maker_fee
+    };
+    let exit_fee = if bot_params.close_execution_policy == ExecutionPolicy::ImmediateOrCancel {
+        exchange_params.taker_fee
+    } else {
+        exchange_params.maker_fee
+    };
+    entry_fee + exit_fee
+}
+
+/// Grid close price before the execution-policy clamp: `position.price` offset by the effective
+/// markup, blended with a `twap_bands.upper`-offset price (weighted by `close_grid_twap_weight`)
+/// when `close_grid_twap_dist` is set.
+fn calc_grid_close_ref_price_long(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    markup: f64,
+) -> f64 {
+    let markup_price = round_to_step(position.price * (1.0 + markup), exchange_params.price_step);
+    if bot_params.close_grid_twap_dist != 0.0 && state_params.twap_bands.upper > 0.0 {
+        let twap_price = round_to_step(
+            state_params.twap_bands.upper * (1.0 + bot_params.close_grid_twap_dist),
+            exchange_params.price_step,
+        );
+        let weight = bot_params.close_grid_twap_weight.clamp(0.0, 1.0);
+        markup_price * (1.0 - weight) + twap_price * weight
+    } else {
+        markup_price
+    }
+}
+
+/// Mirror of [`calc_grid_close_ref_price_long`] for short positions.
+fn calc_grid_close_ref_price_short(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    markup: f64,
+) -> f64 {
+    let markup_price = round_to_step(position.price * (1.0 - markup), exchange_params.price_step);
+    if bot_params.close_grid_twap_dist != 0.0 && state_params.twap_bands.lower > 0.0 {
+        let twap_price = round_to_step(
+            state_params.twap_bands.lower * (1.0 - bot_params.close_grid_twap_dist),
+            exchange_params.price_step,
+        );
+        let weight = bot_params.close_grid_twap_weight.clamp(0.0, 1.0);
+        markup_price * (1.0 - weight) + twap_price * weight
+    } else {
+        markup_price
+    }
+}
+
+pub fn calc_grid_close_long(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+) -> Order {
+    if position.size <= 0.0 {
+        return Order::empty();
+    }
+    let effective_min_markup =
+        bot_params.close_grid_min_markup + round_trip_fee_frac(exchange_params, bot_params);
+    let markup = effective_min_markup + bot_params.close_grid_markup_range;
+    let price = apply_execution_policy_sell(
+        calc_grid_close_ref_price_long(exchange_params, state_params, bot_params, position, markup),
+        state_params.order_book.bid,
+        exchange_params.price_step,
+        bot_params.close_execution_policy,
+    );
+    let qty = round_to_step(
+        (position.size * bot_params.close_grid_qty_pct).min(position.size),
+        exchange_params.qty_step,
+    )
+    .max(exchange_params.min_qty);
+    Order {
+        qty: -qty,
+        price,
+        order_type: OrderType::CloseGridLong,
+    }
+}
+
+pub fn calc_grid_close_short(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+) -> Order {
+    if position.size >= 0.0 {
+        return Order::empty();
+    }
+    let effective_min_markup =
+        bot_params.close_grid_min_markup + round_trip_fee_frac(exchange_params, bot_params);
+    let markup = effective_min_markup + bot_params.close_grid_markup_range;
+    let price = apply_execution_policy_buy(
+        calc_grid_close_ref_price_short(exchange_params, state_params, bot_params, position, markup),
+        state_params.order_book.ask,
+        exchange_params.price_step,
+        bot_params.close_execution_policy,
+    );
+    let qty = round_to_step(
+        (position.size.abs() * bot_params.close_grid_qty_pct).min(position.size.abs()),
+        exchange_params.qty_step,
+    )
+    .max(exchange_params.min_qty);
+    Order {
+        qty,
+        price,
+        order_type: OrderType::CloseGridShort,
+    }
+}
+
+pub fn calc_trailing_close_long(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+) -> Order {
+    if position.size <= 0.0 {
+        return Order::empty();
+    }
+    let retraced = trailing_price_bundle.max_since_open
+        >= position.price * (1.0 + bot_params.close_trailing_threshold_pct)
+        && state_params.order_book.ask
+            <= trailing_price_bundle.max_since_open
+                * (1.0 - bot_params.close_trailing_retracement_pct);
+    if !retraced {
+        return Order::empty();
+    }
+    let price = round_to_step(state_params.order_book.ask, exchange_params.price_step);
+    let qty = round_to_step(
+        (position.size * bot_params.close_trailing_qty_pct).min(position.size),
+        exchange_params.qty_step,
+    )
+    .max(exchange_params.min_qty);
+    Order {
+        qty: -qty,
+        price,
+        order_type: OrderType::CloseTrailingLong,
+    }
+}
+
+pub fn calc_trailing_close_short(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+) -> Order {
+    if position.size >= 0.0 {
+        return Order::empty();
+    }
+    let retraced = trailing_price_bundle.min_since_open
+        <= position.price * (1.0 - bot_params.close_trailing_threshold_pct)
+        && state_params.order_book.bid
+            >= trailing_price_bundle.min_since_open
+                * (1.0 + bot_params.close_trailing_retracement_pct);
+    if !retraced {
+        return Order::empty();
+    }
+    let price = round_to_step(state_params.order_book.bid, exchange_params.price_step);
+    let qty = round_to_step(
+        (position.size.abs() * bot_params.close_trailing_qty_pct).min(position.size.abs()),
+        exchange_params.qty_step,
+    )
+    .max(exchange_params.min_qty);
+    Order {
+        qty,
+        price,
+        order_type: OrderType::CloseTrailingShort,
+    }
+}
+
+/// Returns a market-style close once the stop level has been breached, i.e. once `order_book.ask`
+/// has dropped to or below `position_price * (1 - close_stop_loss_pct)`. Empty when
+/// `close_stop_loss_pct` is 0 (disabled) or the stop hasn't triggered yet.
+pub fn calc_stop_close_long(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+) -> Order {
+    if position.size <= 0.0 || bot_params.close_stop_loss_pct <= 0.0 {
+        return Order::empty();
+    }
+    let stop_price = position.price * (1.0 - bot_params.close_stop_loss_pct);
+    if state_params.order_book.ask > stop_price {
+        return Order::empty();
+    }
+    let price = round_to_step(stop_price, exchange_params.price_step);
+    let qty = round_to_step(
+        (position.size * bot_params.close_stop_loss_qty_pct).min(position.size),
+        exchange_params.qty_step,
+    )
+    .max(exchange_params.min_qty);
+    Order {
+        qty: -qty,
+        price,
+        order_type: OrderType::CloseStopLossLong,
+    }
+}
+
+/// Mirror of [`calc_stop_close_long`] for short positions: triggers once `order_book.bid` has
+/// risen to or above `position_price * (1 + close_stop_loss_pct)`.
+pub fn calc_stop_close_short(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+) -> Order {
+    if position.size >= 0.0 || bot_params.close_stop_loss_pct <= 0.0 {
+        return Order::empty();
+    }
+    let stop_price = position.price * (1.0 + bot_params.close_stop_loss_pct);
+    if state_params.order_book.bid < stop_price {
+        return Order::empty();
+    }
+    let price = round_to_step(stop_price, exchange_params.price_step);
+    let qty = round_to_step(
+        (position.size.abs() * bot_params.close_stop_loss_qty_pct).min(position.size.abs()),
+        exchange_params.qty_step,
+    )
+    .max(exchange_params.min_qty);
+    Order {
+        qty,
+        price,
+        order_type: OrderType::CloseStopLossShort,
+    }
+}
+
+/// Dutch-auction close: instead of the static `close_grid_min_markup..close_grid_markup_range`
+/// band, the required markup decays linearly from `close_auction_start_markup` toward
+/// `close_auction_end_markup` as `candles_since_open` approaches `close_auction_duration_candles`,
+/// so a position that refuses to close at the ideal markup progressively lowers its target.
+/// Empty when `close_auction_duration_candles` is 0 (disabled).
+pub fn calc_auction_close_long(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    candles_since_open: usize,
+) -> Order {
+    if position.size <= 0.0 || bot_params.close_auction_duration_candles == 0 {
+        return Order::empty();
+    }
+    let t = (candles_since_open as f64 / bot_params.close_auction_duration_candles as f64)
+        .clamp(0.0, 1.0);
+    let markup = bot_params.close_auction_start_markup
+        + (bot_params.close_auction_end_markup - bot_params.close_auction_start_markup) * t;
+    let price = apply_execution_policy_sell(
+        round_to_step(position.price * (1.0 + markup), exchange_params.price_step),
+        state_params.order_book.bid,
+        exchange_params.price_step,
+        bot_params.close_execution_policy,
+    );
+    let qty = round_to_step(
+        (position.size * bot_params.close_grid_qty_pct).min(position.size),
+        exchange_params.qty_step,
+    )
+    .max(exchange_params.min_qty);
+    Order {
+        qty: -qty,
+        price,
+        order_type: OrderType::CloseAuctionLong,
+    }
+}
+
+/// Mirror of [`calc_auction_close_long`] for short positions.
+pub fn calc_auction_close_short(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    candles_since_open: usize,
+) -> Order {
+    if position.size >= 0.0 || bot_params.close_auction_duration_candles == 0 {
+        return Order::empty();
+    }
+    let t = (candles_since_open as f64 / bot_params.close_auction_duration_candles as f64)
+        .clamp(0.0, 1.0);
+    let markup = bot_params.close_auction_start_markup
+        + (bot_params.close_auction_end_markup - bot_params.close_auction_start_markup) * t;
+    let price = apply_execution_policy_buy(
+        round_to_step(position.price * (1.0 - markup), exchange_params.price_step),
+        state_params.order_book.ask,
+        exchange_params.price_step,
+        bot_params.close_execution_policy,
+    );
+    let qty = round_to_step(
+        (position.size.abs() * bot_params.close_grid_qty_pct).min(position.size.abs()),
+        exchange_params.qty_step,
+    )
+    .max(exchange_params.min_qty);
+    Order {
+        qty,
+        price,
+        order_type: OrderType::CloseAuctionShort,
+    }
+}
+
+/// Returns either the static grid close or the Dutch-auction close, depending on whether
+/// `close_auction_duration_candles` is set.
+fn calc_grid_or_auction_close_long(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    candles_since_open: usize,
+) -> Order {
+    if bot_params.close_auction_duration_candles > 0 {
+        calc_auction_close_long(
+            exchange_params,
+            state_params,
+            bot_params,
+            position,
+            candles_since_open,
+        )
+    } else {
+        calc_grid_close_long(exchange_params, state_params, bot_params, position)
+    }
+}
+
+/// Mirror of [`calc_grid_or_auction_close_long`] for short positions.
+fn calc_grid_or_auction_close_short(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    candles_since_open: usize,
+) -> Order {
+    if bot_params.close_auction_duration_candles > 0 {
+        calc_auction_close_short(
+            exchange_params,
+            state_params,
+            bot_params,
+            position,
+            candles_since_open,
+        )
+    } else {
+        calc_grid_close_short(exchange_params, state_params, bot_params, position)
+    }
+}
+
+/// Returns the linked take-profit (grid) and stop-loss legs of an OCA bracket for a long
+/// position. The caller is responsible for treating them as one-cancels-the-other: once one
+/// fills, the other must be dropped for the filled quantity.
+pub fn calc_bracket_close_long(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+) -> (Order, Order) {
+    let tp_order = calc_grid_close_long(exchange_params, state_params, bot_params, position);
+    let stop_order = calc_stop_close_long(exchange_params, state_params, bot_params, position);
+    (tp_order, stop_order)
+}
+
+/// Mirror of [`calc_bracket_close_long`] for short positions.
+pub fn calc_bracket_close_short(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+) -> (Order, Order) {
+    let tp_order = calc_grid_close_short(exchange_params, state_params, bot_params, position);
+    let stop_order = calc_stop_close_short(exchange_params, state_params, bot_params, position);
+    (tp_order, stop_order)
+}
+
+pub fn calc_next_close_long(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+    candles_since_open: usize,
+) -> Order {
+    if bot_params.close_bracket_mode {
+        let (tp_order, stop_order) =
+            calc_bracket_close_long(exchange_params, state_params, bot_params, position);
+        let ask = state_params.order_book.ask;
+        return match (tp_order.qty != 0.0, stop_order.qty != 0.0) {
+            (true, true) => {
+                if (tp_order.price - ask).abs() <= (stop_order.price - ask).abs() {
+                    tp_order
+                } else {
+                    stop_order
+                }
+            }
+            (true, false) => tp_order,
+            (false, true) => stop_order,
+            (false, false) => Order::empty(),
+        };
+    }
+    let stop_order = calc_stop_close_long(exchange_params, state_params, bot_params, position);
+    if stop_order.qty != 0.0 {
+        return stop_order;
+    }
+    if bot_params.close_trailing_grid_ratio == 0.0 {
+        return calc_grid_or_auction_close_long(
+            exchange_params,
+            state_params,
+            bot_params,
+            position,
+            candles_since_open,
+        );
+    }
+    if bot_params.close_trailing_grid_ratio >= 1.0 {
+        return calc_trailing_close_long(
+            exchange_params,
+            state_params,
+            bot_params,
+            position,
+            trailing_price_bundle,
+        );
+    }
+    let trailing_order = calc_trailing_close_long(
+        exchange_params,
+        state_params,
+        bot_params,
+        position,
+        trailing_price_bundle,
+    );
+    if trailing_order.qty == 0.0 {
+        calc_grid_or_auction_close_long(
+            exchange_params,
+            state_params,
+            bot_params,
+            position,
+            candles_since_open,
+        )
+    } else {
+        trailing_order
+    }
+}
+
+pub fn calc_next_close_short(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+    candles_since_open: usize,
+) -> Order {
+    if bot_params.close_bracket_mode {
+        let (tp_order, stop_order) =
+            calc_bracket_close_short(exchange_params, state_params, bot_params, position);
+        let bid = state_params.order_book.bid;
+        return match (tp_order.qty != 0.0, stop_order.qty != 0.0) {
+            (true, true) => {
+                if (tp_order.price - bid).abs() <= (stop_order.price - bid).abs() {
+                    tp_order
+                } else {
+                    stop_order
+                }
+            }
+            (true, false) => tp_order,
+            (false, true) => stop_order,
+            (false, false) => Order::empty(),
+        };
+    }
+    let stop_order = calc_stop_close_short(exchange_params, state_params, bot_params, position);
+    if stop_order.qty != 0.0 {
+        return stop_order;
+    }
+    if bot_params.close_trailing_grid_ratio == 0.0 {
+        return calc_grid_or_auction_close_short(
+            exchange_params,
+            state_params,
+            bot_params,
+            position,
+            candles_since_open,
+        );
+    }
+    if bot_params.close_trailing_grid_ratio >= 1.0 {
+        return calc_trailing_close_short(
+            exchange_params,
+            state_params,
+            bot_params,
+            position,
+            trailing_price_bundle,
+        );
+    }
+    let trailing_order = calc_trailing_close_short(
+        exchange_params,
+        state_params,
+        bot_params,
+        position,
+        trailing_price_bundle,
+    );
+    if trailing_order.qty == 0.0 {
+        calc_grid_or_auction_close_short(
+            exchange_params,
+            state_params,
+            bot_params,
+            position,
+            candles_since_open,
+        )
+    } else {
+        trailing_order
+    }
+}
+
+pub fn calc_closes_long(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+    candles_since_open: usize,
+) -> Vec<Order> {
+    let mut orders = Vec::new();
+    let mut sim_position = *position;
+    for _ in 0..8 {
+        let order = calc_next_close_long(
+            exchange_params,
+            state_params,
+            bot_params,
+            &sim_position,
+            trailing_price_bundle,
+            candles_since_open,
+        );
+        if order.qty >= 0.0 {
+            break;
+        }
+        sim_position.size = (sim_position.size + order.qty).max(0.0);
+        orders.push(order);
+        if sim_position.size <= 0.0 {
+            break;
+        }
+    }
+    orders
+}
+
+pub fn calc_closes_short(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+    candles_since_open: usize,
+) -> Vec<Order> {
+    let mut orders = Vec::new();
+    let mut sim_position = *position;
+    for _ in 0..8 {
+        let order = calc_next_close_short(
+            exchange_params,
+            state_params,
+            bot_params,
+            &sim_position,
+            trailing_price_bundle,
+            candles_since_open,
+        );
+        if order.qty <= 0.0 {
+            break;
+        }
+        sim_position.size = (sim_position.size + order.qty).min(0.0);
+        orders.push(order);
+        if sim_position.size >= 0.0 {
+            break;
+        }
+    }
+    orders
+}
+
+/// Reconciles `original_plan` (the most recent [`calc_closes_long`] output) against a partial
+/// fill that just landed at `filled_price`/`filled_qty`: the planned node nearest `filled_price`
+/// (within half a `price_step`) has the filled quantity subtracted from it instead of the whole
+/// ladder being thrown away, and the remaining close nodes are re-derived from the updated
+/// position. Closes never move `position.price`, so reconciliation only touches `size`.
+pub fn recalc_closes_after_partial_fill_long(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+    candles_since_open: usize,
+    original_plan: &[Order],
+    filled_qty: f64,
+    filled_price: f64,
+) -> Vec<Order> {
+    if filled_qty >= 0.0 {
+        return calc_closes_long(
+            exchange_params,
+            state_params,
+            bot_params,
+            position,
+            trailing_price_bundle,
+            candles_since_open,
+        );
+    }
+    let mut sim_position = *position;
+    sim_position.size = (sim_position.size + filled_qty).max(0.0);
+
+    let mut orders = Vec::new();
+    if let Some(node) = original_plan
+        .iter()
+        .filter(|o| o.qty < 0.0)
+        .min_by(|a, b| {
+            (a.price - filled_price)
+                .abs()
+                .total_cmp(&(b.price - filled_price).abs())
+        })
+    {
+        if (node.price - filled_price).abs() <= exchange_params.price_step / 2.0 {
+            let residual_qty =
+                round_to_step((node.qty.abs() - filled_qty.abs()).max(0.0), exchange_params.qty_step);
+            if residual_qty >= exchange_params.min_qty {
+                orders.push(Order {
+                    qty: -residual_qty,
+                    price: node.price,
+                    order_type: node.order_type,
+                });
+            }
+        }
+    }
+
+    for _ in 0..8 {
+        let order = calc_next_close_long(
+            exchange_params,
+            state_params,
+            bot_params,
+            &sim_position,
+            trailing_price_bundle,
+            candles_since_open,
+        );
+        if order.qty >= 0.0 {
+            break;
+        }
+        sim_position.size = (sim_position.size + order.qty).max(0.0);
+        orders.push(order);
+        if sim_position.size <= 0.0 {
+            break;
+        }
+    }
+    orders
+}
+
+/// Mirror of [`recalc_closes_after_partial_fill_long`] for short positions.
+pub fn recalc_closes_after_partial_fill_short(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+    candles_since_open: usize,
+    original_plan: &[Order],
+    filled_qty: f64,
+    filled_price: f64,
+) -> Vec<Order> {
+    if filled_qty <= 0.0 {
+        return calc_closes_short(
+            exchange_params,
+            state_params,
+            bot_params,
+            position,
+            trailing_price_bundle,
+            candles_since_open,
+        );
+    }
+    let mut sim_position = *position;
+    sim_position.size = (sim_position.size + filled_qty).min(0.0);
+
+    let mut orders = Vec::new();
+    if let Some(node) = original_plan
+        .iter()
+        .filter(|o| o.qty > 0.0)
+        .min_by(|a, b| (a.price - filled_price).abs().total_cmp(&(b.price - filled_price).abs()))
+    {
+        if (node.price - filled_price).abs() <= exchange_params.price_step / 2.0 {
+            let residual_qty =
+                round_to_step((node.qty - filled_qty).max(0.0), exchange_params.qty_step);
+            if residual_qty >= exchange_params.min_qty {
+                orders.push(Order {
+                    qty: residual_qty,
+                    price: node.price,
+                    order_type: node.order_type,
+                });
+            }
+        }
+    }
+
+    for _ in 0..8 {
+        let order = calc_next_close_short(
+            exchange_params,
+            state_params,
+            bot_params,
+            &sim_position,
+            trailing_price_bundle,
+            candles_since_open,
+        );
+        if order.qty <= 0.0 {
+            break;
+        }
+        sim_position.size = (sim_position.size + order.qty).min(0.0);
+        orders.push(order);
+        if sim_position.size >= 0.0 {
+            break;
+        }
+    }
+    orders
+}
@@ -0,0 +1,611 @@
+use crate::closes::{calc_grid_close_long, calc_grid_close_short};
+use crate::types::{
+    BotParams, ExchangeParams, ExecutionPolicy, Order, OrderType, Position, SelfTradeBehavior,
+    StateParams, TrailingPriceBundle,
+};
+
+fn round_to_step(value: f64, step: f64) -> f64 {
+    if step <= 0.0 {
+        return value;
+    }
+    (value / step).round() * step
+}
+
+fn cost_to_qty(cost: f64, price: f64, c_mult: f64) -> f64 {
+    if price <= 0.0 || c_mult <= 0.0 {
+        return 0.0;
+    }
+    cost / (price * c_mult)
+}
+
+/// Scales the initial entry qty inversely to recent volatility: choppier-than-usual coins
+/// (`noisiness_now` above the symbol's `noisiness_median`) get a smaller base position.
+/// Returns 1.0 (flat sizing) when scaling is disabled or there's no median to compare against.
+fn calc_vol_scale(bot_params: &BotParams, state_params: &StateParams) -> f64 {
+    if bot_params.entry_qty_vol_scaling <= 0.0 || state_params.noisiness_median <= 0.0 {
+        return 1.0;
+    }
+    let ratio = state_params.noisiness_now / state_params.noisiness_median;
+    let scale = 1.0 / (1.0 + bot_params.entry_qty_vol_scaling * (ratio - 1.0));
+    scale.clamp(0.1, 10.0)
+}
+
+/// Applies `policy` to a buy-side (long entry / short close) grid price: `PostOnly` clamps it
+/// below the best ask so it always rests as maker, `ImmediateOrCancel` forces it to the ask so
+/// it fills immediately as taker, `Limit` leaves it untouched.
+fn apply_execution_policy_buy(price: f64, ask: f64, price_step: f64, policy: ExecutionPolicy) -> f64 {
+    if ask <= 0.0 {
+        return price;
+    }
+    match policy {
+        ExecutionPolicy::Limit => price,
+        ExecutionPolicy::PostOnly => price.min(round_to_step(ask - price_step, price_step)),
+        ExecutionPolicy::ImmediateOrCancel => round_to_step(ask, price_step),
+    }
+}
+
+/// Mirror of [`apply_execution_policy_buy`] for sell-side (short entry / long close) grid prices.
+fn apply_execution_policy_sell(price: f64, bid: f64, price_step: f64, policy: ExecutionPolicy) -> f64 {
+    if bid <= 0.0 {
+        return price;
+    }
+    match policy {
+        ExecutionPolicy::Limit => price,
+        ExecutionPolicy::PostOnly => price.max(round_to_step(bid + price_step, price_step)),
+        ExecutionPolicy::ImmediateOrCancel => round_to_step(bid, price_step),
+    }
+}
+
+/// Round-trip fee cost (as a fraction of position price) of entering and then closing through
+/// the grid, each leg priced as maker or taker depending on its configured execution policy.
+/// Negative where a leg earns a maker rebate.
+fn round_trip_fee_frac(exchange_params: &ExchangeParams, bot_params: &BotParams) -> f64 {
+    let entry_fee = if bot_params.entry_execution_policy == ExecutionPolicy::ImmediateOrCancel {
+        exchange_params.taker_fee
+    } else {
+        exchange_params.maker_fee
+    };
+    let exit_fee = if bot_params.close_execution_policy == ExecutionPolicy::ImmediateOrCancel {
+        exchange_params.taker_fee
+    } else {
+        exchange_params.maker_fee
+    };
+    entry_fee + exit_fee
+}
+
+/// True if a long entry at `price` would cross the symbol's own resting grid close and
+/// `self_trade_behavior` is configured to suppress that rather than let it decrement normally.
+fn crosses_self_long(
+    price: f64,
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+) -> bool {
+    if bot_params.self_trade_behavior == SelfTradeBehavior::DecrementTake {
+        return false;
+    }
+    let sibling = calc_grid_close_long(exchange_params, state_params, bot_params, position);
+    sibling.qty != 0.0 && price >= sibling.price
+}
+
+/// Reference price for the initial long entry: the order-book-offset price, blended with a
+/// `twap_bands.lower`-offset price (weighted by `entry_initial_twap_weight`) when
+/// `entry_initial_twap_dist` is set.
+fn calc_initial_entry_ref_price_long(state_params: &StateParams, bot_params: &BotParams) -> f64 {
+    let ob_price = state_params.order_book.bid * (1.0 - bot_params.entry_initial_ema_dist);
+    if bot_params.entry_initial_twap_dist != 0.0 && state_params.twap_bands.lower > 0.0 {
+        let twap_price = state_params.twap_bands.lower * (1.0 - bot_params.entry_initial_twap_dist);
+        let weight = bot_params.entry_initial_twap_weight.clamp(0.0, 1.0);
+        ob_price * (1.0 - weight) + twap_price * weight
+    } else {
+        ob_price
+    }
+}
+
+/// Mirror of [`calc_initial_entry_ref_price_long`] for the initial short entry.
+fn calc_initial_entry_ref_price_short(state_params: &StateParams, bot_params: &BotParams) -> f64 {
+    let ob_price = state_params.order_book.ask * (1.0 + bot_params.entry_initial_ema_dist);
+    if bot_params.entry_initial_twap_dist != 0.0 && state_params.twap_bands.upper > 0.0 {
+        let twap_price = state_params.twap_bands.upper * (1.0 + bot_params.entry_initial_twap_dist);
+        let weight = bot_params.entry_initial_twap_weight.clamp(0.0, 1.0);
+        ob_price * (1.0 - weight) + twap_price * weight
+    } else {
+        ob_price
+    }
+}
+
+/// Mirror of [`crosses_self_long`] for short entries vs. the resting grid close short.
+fn crosses_self_short(
+    price: f64,
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+) -> bool {
+    if bot_params.self_trade_behavior == SelfTradeBehavior::DecrementTake {
+        return false;
+    }
+    let sibling = calc_grid_close_short(exchange_params, state_params, bot_params, position);
+    sibling.qty != 0.0 && price <= sibling.price
+}
+
+pub fn calc_grid_entry_long(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+) -> Order {
+    if bot_params.wallet_exposure_limit <= 0.0 || state_params.order_book.bid <= 0.0 {
+        return Order::empty();
+    }
+    if position.size == 0.0 {
+        let mut price = round_to_step(
+            calc_initial_entry_ref_price_long(state_params, bot_params),
+            exchange_params.price_step,
+        );
+        price = apply_execution_policy_buy(
+            price,
+            state_params.order_book.ask,
+            exchange_params.price_step,
+            bot_params.entry_execution_policy,
+        );
+        if crosses_self_long(price, exchange_params, state_params, bot_params, position) {
+            return Order::empty();
+        }
+        let cost = state_params.balance * bot_params.wallet_exposure_limit
+            * bot_params.entry_initial_qty_pct;
+        let vol_scale = calc_vol_scale(bot_params, state_params);
+        let qty = round_to_step(
+            cost_to_qty(cost, price, exchange_params.c_mult) * vol_scale,
+            exchange_params.qty_step,
+        )
+        .max(exchange_params.min_qty);
+        return Order {
+            qty,
+            price,
+            order_type: OrderType::EntryInitialNormalLong,
+        };
+    }
+    let effective_spacing_pct =
+        bot_params.entry_grid_spacing_pct + round_trip_fee_frac(exchange_params, bot_params);
+    let mut price = round_to_step(
+        position.price * (1.0 - effective_spacing_pct),
+        exchange_params.price_step,
+    );
+    price = apply_execution_policy_buy(
+        price,
+        state_params.order_book.ask,
+        exchange_params.price_step,
+        bot_params.entry_execution_policy,
+    );
+    if crosses_self_long(price, exchange_params, state_params, bot_params, position) {
+        return Order::empty();
+    }
+    let cost = position.size * position.price * bot_params.entry_grid_double_down_factor;
+    let qty = round_to_step(
+        cost_to_qty(cost, price, exchange_params.c_mult),
+        exchange_params.qty_step,
+    )
+    .max(exchange_params.min_qty);
+    Order {
+        qty,
+        price,
+        order_type: OrderType::EntryGridNormalLong,
+    }
+}
+
+pub fn calc_grid_entry_short(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+) -> Order {
+    if bot_params.wallet_exposure_limit <= 0.0 || state_params.order_book.ask <= 0.0 {
+        return Order::empty();
+    }
+    if position.size == 0.0 {
+        let mut price = round_to_step(
+            calc_initial_entry_ref_price_short(state_params, bot_params),
+            exchange_params.price_step,
+        );
+        price = apply_execution_policy_sell(
+            price,
+            state_params.order_book.bid,
+            exchange_params.price_step,
+            bot_params.entry_execution_policy,
+        );
+        if crosses_self_short(price, exchange_params, state_params, bot_params, position) {
+            return Order::empty();
+        }
+        let cost = state_params.balance * bot_params.wallet_exposure_limit
+            * bot_params.entry_initial_qty_pct;
+        let vol_scale = calc_vol_scale(bot_params, state_params);
+        let qty = -round_to_step(
+            cost_to_qty(cost, price, exchange_params.c_mult) * vol_scale,
+            exchange_params.qty_step,
+        )
+        .max(exchange_params.min_qty);
+        return Order {
+            qty,
+            price,
+            order_type: OrderType::EntryInitialNormalShort,
+        };
+    }
+    let effective_spacing_pct =
+        bot_params.entry_grid_spacing_pct + round_trip_fee_frac(exchange_params, bot_params);
+    let mut price = round_to_step(
+        position.price * (1.0 + effective_spacing_pct),
+        exchange_params.price_step,
+    );
+    price = apply_execution_policy_sell(
+        price,
+        state_params.order_book.bid,
+        exchange_params.price_step,
+        bot_params.entry_execution_policy,
+    );
+    if crosses_self_short(price, exchange_params, state_params, bot_params, position) {
+        return Order::empty();
+    }
+    let cost = position.size.abs() * position.price * bot_params.entry_grid_double_down_factor;
+    let qty = -round_to_step(
+        cost_to_qty(cost, price, exchange_params.c_mult),
+        exchange_params.qty_step,
+    )
+    .max(exchange_params.min_qty);
+    Order {
+        qty,
+        price,
+        order_type: OrderType::EntryGridNormalShort,
+    }
+}
+
+pub fn calc_trailing_entry_long(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+) -> Order {
+    if position.size == 0.0 {
+        return calc_grid_entry_long(exchange_params, state_params, bot_params, position);
+    }
+    let retraced = trailing_price_bundle.max_since_min
+        >= trailing_price_bundle.min_since_open
+            * (1.0 + bot_params.entry_trailing_threshold_pct)
+        && state_params.order_book.bid
+            <= trailing_price_bundle.max_since_min
+                * (1.0 - bot_params.entry_trailing_retracement_pct);
+    if !retraced {
+        return Order::empty();
+    }
+    let price = round_to_step(state_params.order_book.bid, exchange_params.price_step);
+    let cost = position.size * position.price * bot_params.entry_grid_double_down_factor;
+    let qty = round_to_step(
+        cost_to_qty(cost, price, exchange_params.c_mult),
+        exchange_params.qty_step,
+    )
+    .max(exchange_params.min_qty);
+    Order {
+        qty,
+        price,
+        order_type: OrderType::EntryTrailingNormalLong,
+    }
+}
+
+pub fn calc_trailing_entry_short(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+) -> Order {
+    if position.size == 0.0 {
+        return calc_grid_entry_short(exchange_params, state_params, bot_params, position);
+    }
+    let retraced = trailing_price_bundle.min_since_max
+        <= trailing_price_bundle.max_since_open
+            * (1.0 - bot_params.entry_trailing_threshold_pct)
+        && state_params.order_book.ask
+            >= trailing_price_bundle.min_since_max
+                * (1.0 + bot_params.entry_trailing_retracement_pct);
+    if !retraced {
+        return Order::empty();
+    }
+    let price = round_to_step(state_params.order_book.ask, exchange_params.price_step);
+    let cost = position.size.abs() * position.price * bot_params.entry_grid_double_down_factor;
+    let qty = -round_to_step(
+        cost_to_qty(cost, price, exchange_params.c_mult),
+        exchange_params.qty_step,
+    )
+    .max(exchange_params.min_qty);
+    Order {
+        qty,
+        price,
+        order_type: OrderType::EntryTrailingNormalShort,
+    }
+}
+
+pub fn calc_next_entry_long(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+) -> Order {
+    if bot_params.entry_trailing_grid_ratio == 0.0 {
+        return calc_grid_entry_long(exchange_params, state_params, bot_params, position);
+    }
+    if bot_params.entry_trailing_grid_ratio >= 1.0 {
+        return calc_trailing_entry_long(
+            exchange_params,
+            state_params,
+            bot_params,
+            position,
+            trailing_price_bundle,
+        );
+    }
+    let grid_order = calc_grid_entry_long(exchange_params, state_params, bot_params, position);
+    let trailing_order = calc_trailing_entry_long(
+        exchange_params,
+        state_params,
+        bot_params,
+        position,
+        trailing_price_bundle,
+    );
+    if trailing_order.qty == 0.0 {
+        grid_order
+    } else {
+        trailing_order
+    }
+}
+
+pub fn calc_next_entry_short(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+) -> Order {
+    if bot_params.entry_trailing_grid_ratio == 0.0 {
+        return calc_grid_entry_short(exchange_params, state_params, bot_params, position);
+    }
+    if bot_params.entry_trailing_grid_ratio >= 1.0 {
+        return calc_trailing_entry_short(
+            exchange_params,
+            state_params,
+            bot_params,
+            position,
+            trailing_price_bundle,
+        );
+    }
+    let grid_order = calc_grid_entry_short(exchange_params, state_params, bot_params, position);
+    let trailing_order = calc_trailing_entry_short(
+        exchange_params,
+        state_params,
+        bot_params,
+        position,
+        trailing_price_bundle,
+    );
+    if trailing_order.qty == 0.0 {
+        grid_order
+    } else {
+        trailing_order
+    }
+}
+
+pub fn calc_entries_long(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+) -> Vec<Order> {
+    let mut orders = Vec::new();
+    let mut sim_position = *position;
+    for _ in 0..8 {
+        let order = calc_next_entry_long(
+            exchange_params,
+            state_params,
+            bot_params,
+            &sim_position,
+            trailing_price_bundle,
+        );
+        if order.qty <= 0.0 {
+            break;
+        }
+        let new_size = sim_position.size + order.qty;
+        sim_position.price = if new_size > 0.0 {
+            (sim_position.size * sim_position.price + order.qty * order.price) / new_size
+        } else {
+            order.price
+        };
+        sim_position.size = new_size;
+        orders.push(order);
+    }
+    orders
+}
+
+/// Reconciles `original_plan` (the most recent [`calc_entries_long`] output) against a partial
+/// fill that just landed at `filled_price`/`filled_qty`: the planned node nearest `filled_price`
+/// (within half a `price_step`) has the filled quantity subtracted from it instead of the whole
+/// ladder being thrown away, and the remaining double-down nodes are re-derived from the
+/// updated position.
+pub fn recalc_entries_after_partial_fill_long(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+    original_plan: &[Order],
+    filled_qty: f64,
+    filled_price: f64,
+) -> Vec<Order> {
+    if filled_qty <= 0.0 {
+        return calc_entries_long(
+            exchange_params,
+            state_params,
+            bot_params,
+            position,
+            trailing_price_bundle,
+        );
+    }
+    let new_size = position.size + filled_qty;
+    let mut sim_position = Position {
+        size: new_size,
+        price: if new_size > 0.0 {
+            (position.size * position.price + filled_qty * filled_price) / new_size
+        } else {
+            filled_price
+        },
+    };
+
+    let mut orders = Vec::new();
+    if let Some(node) = original_plan
+        .iter()
+        .filter(|o| o.qty > 0.0)
+        .min_by(|a, b| (a.price - filled_price).abs().total_cmp(&(b.price - filled_price).abs()))
+    {
+        if (node.price - filled_price).abs() <= exchange_params.price_step / 2.0 {
+            let residual_qty =
+                round_to_step((node.qty - filled_qty).max(0.0), exchange_params.qty_step);
+            if residual_qty >= exchange_params.min_qty {
+                orders.push(Order {
+                    qty: residual_qty,
+                    price: node.price,
+                    order_type: node.order_type,
+                });
+            }
+        }
+    }
+
+    for _ in 0..8 {
+        let order = calc_next_entry_long(
+            exchange_params,
+            state_params,
+            bot_params,
+            &sim_position,
+            trailing_price_bundle,
+        );
+        if order.qty <= 0.0 {
+            break;
+        }
+        let new_size = sim_position.size + order.qty;
+        sim_position.price = if new_size > 0.0 {
+            (sim_position.size * sim_position.price + order.qty * order.price) / new_size
+        } else {
+            order.price
+        };
+        sim_position.size = new_size;
+        orders.push(order);
+    }
+    orders
+}
+
+/// Mirror of [`recalc_entries_after_partial_fill_long`] for short positions.
+pub fn recalc_entries_after_partial_fill_short(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+    original_plan: &[Order],
+    filled_qty: f64,
+    filled_price: f64,
+) -> Vec<Order> {
+    if filled_qty >= 0.0 {
+        return calc_entries_short(
+            exchange_params,
+            state_params,
+            bot_params,
+            position,
+            trailing_price_bundle,
+        );
+    }
+    let new_size = position.size + filled_qty;
+    let mut sim_position = Position {
+        size: new_size,
+        price: if new_size < 0.0 {
+            (position.size * position.price + filled_qty * filled_price) / new_size
+        } else {
+            filled_price
+        },
+    };
+
+    let mut orders = Vec::new();
+    if let Some(node) = original_plan
+        .iter()
+        .filter(|o| o.qty < 0.0)
+        .min_by(|a, b| {
+            (a.price - filled_price)
+                .abs()
+                .total_cmp(&(b.price - filled_price).abs())
+        })
+    {
+        if (node.price - filled_price).abs() <= exchange_params.price_step / 2.0 {
+            let residual_qty =
+                round_to_step((node.qty.abs() - filled_qty.abs()).max(0.0), exchange_params.qty_step);
+            if residual_qty >= exchange_params.min_qty {
+                orders.push(Order {
+                    qty: -residual_qty,
+                    price: node.price,
+                    order_type: node.order_type,
+                });
+            }
+        }
+    }
+
+    for _ in 0..8 {
+        let order = calc_next_entry_short(
+            exchange_params,
+            state_params,
+            bot_params,
+            &sim_position,
+            trailing_price_bundle,
+        );
+        if order.qty >= 0.0 {
+            break;
+        }
+        let new_size = sim_position.size + order.qty;
+        sim_position.price = if new_size < 0.0 {
+            (sim_position.size * sim_position.price + order.qty * order.price) / new_size
+        } else {
+            order.price
+        };
+        sim_position.size = new_size;
+        orders.push(order);
+    }
+    orders
+}
+
+pub fn calc_entries_short(
+    exchange_params: &ExchangeParams,
+    state_params: &StateParams,
+    bot_params: &BotParams,
+    position: &Position,
+    trailing_price_bundle: &TrailingPriceBundle,
+) -> Vec<Order> {
+    let mut orders = Vec::new();
+    let mut sim_position = *position;
+    for _ in 0..8 {
+        let order = calc_next_entry_short(
+            exchange_params,
+            state_params,
+            bot_params,
+            &sim_position,
+            trailing_price_bundle,
+        );
+        if order.qty >= 0.0 {
+            break;
+        }
+        let new_size = sim_position.size + order.qty;
+        sim_position.price = if new_size < 0.0 {
+            (sim_position.size * sim_position.price + order.qty * order.price) / new_size
+        } else {
+            order.price
+        };
+        sim_position.size = new_size;
+        orders.push(order);
+    }
+    orders
+}
@@ -0,0 +1,46 @@
+pub mod backtest;
+pub mod closes;
+pub mod entries;
+pub mod python;
+pub mod types;
+
+use pyo3::prelude::*;
+
+#[pymodule]
+fn passivbot_rust(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(python::calc_volumes_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_noisiness_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::run_backtest, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_grid_close_long_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_stop_close_long_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_trailing_close_long_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_grid_entry_long_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_trailing_entry_long_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_next_entry_long_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_next_close_long_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_bracket_close_long_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_bracket_close_short_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_next_entry_short_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_next_close_short_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_entries_long_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_entries_short_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_closes_long_py, m)?)?;
+    m.add_function(wrap_pyfunction!(python::calc_closes_short_py, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        python::recalc_entries_after_partial_fill_long_py,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(
+        python::recalc_entries_after_partial_fill_short_py,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(
+        python::recalc_closes_after_partial_fill_long_py,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(
+        python::recalc_closes_after_partial_fill_short_py,
+        m
+    )?)?;
+    Ok(())
+}
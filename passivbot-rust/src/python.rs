@@ -1,15 +1,18 @@
-use crate::backtest::{analyze_backtest, calc_noisiness, calc_volumes, Backtest};
+use crate::backtest::{analyze_backtest, calc_noisiness, calc_volumes, run_monte_carlo, Backtest};
 use crate::closes::{
-    calc_closes_long, calc_closes_short, calc_grid_close_long, calc_next_close_long,
-    calc_next_close_short, calc_trailing_close_long,
+    calc_bracket_close_long, calc_bracket_close_short, calc_closes_long, calc_closes_short,
+    calc_grid_close_long, calc_next_close_long, calc_next_close_short, calc_stop_close_long,
+    calc_trailing_close_long, recalc_closes_after_partial_fill_long,
+    recalc_closes_after_partial_fill_short,
 };
 use crate::entries::{
     calc_entries_long, calc_entries_short, calc_grid_entry_long, calc_next_entry_long,
-    calc_next_entry_short, calc_trailing_entry_long,
+    calc_next_entry_short, calc_trailing_entry_long, recalc_entries_after_partial_fill_long,
+    recalc_entries_after_partial_fill_short,
 };
 use crate::types::{
     Analysis, BacktestParams, BotParams, BotParamsPair, EMABands, ExchangeParams, Order, OrderBook,
-    Position, StateParams, TrailingPriceBundle,
+    OrderType, Position, StateParams, TrailingPriceBundle, TwapBands,
 };
 use ndarray::{Array1, Array2, Array3, Array4, ArrayBase, ArrayD};
 use numpy::{
@@ -49,14 +52,20 @@ pub fn calc_noisiness_py(
 }
 
 #[pyfunction]
+#[pyo3(signature = (hlcs, preferred_coins, bot_params_pair_dict, exchange_params_list, backtest_params_dict, funding_rates=None))]
 pub fn run_backtest(
     hlcs: PyReadonlyArray3<f64>,
     preferred_coins: &PyAny,
     bot_params_pair_dict: &PyDict,
     exchange_params_list: &PyAny,
     backtest_params_dict: &PyDict,
+    funding_rates: Option<PyReadonlyArray2<f64>>,
 ) -> PyResult<(Py<PyArray2<PyObject>>, Py<PyArray1<f64>>, Py<PyDict>)> {
     let hlcs_rust = hlcs.as_array();
+    let funding_rates_rust: Array2<f64> = match funding_rates {
+        Some(arr) => arr.as_array().to_owned(),
+        None => Array2::zeros((hlcs_rust.shape()[0], hlcs_rust.shape()[1])),
+    };
 
     let preferred_coins_rust: Array2<i32> =
         if let Ok(arr) = preferred_coins.downcast::<PyArray2<i32>>() {
@@ -100,6 +109,7 @@ pub fn run_backtest(
         bot_params_pair,
         exchange_params,
         &backtest_params,
+        funding_rates_rust,
     );
 
     // Run the backtest and get fills and equities
@@ -117,6 +127,32 @@ pub fn run_backtest(
         )?;
         py_analysis.set_item("equity_balance_diff_max", analysis.equity_balance_diff_max)?;
         py_analysis.set_item("loss_profit_ratio", analysis.loss_profit_ratio)?;
+        py_analysis.set_item("total_funding_paid", analysis.total_funding_paid)?;
+
+        if backtest_params.mc_runs > 1 {
+            let mc_analysis = run_monte_carlo(
+                &fills,
+                backtest_params.starting_balance,
+                backtest_params.mc_runs,
+                backtest_params.mc_price_jitter_pct,
+                backtest_params.mc_qty_jitter_pct,
+                equities.len(),
+            );
+            let py_mc = PyDict::new(py);
+            for (name, metric) in [
+                ("adg", mc_analysis.adg),
+                ("mdg", mc_analysis.mdg),
+                ("sharpe_ratio", mc_analysis.sharpe_ratio),
+                ("drawdown_worst", mc_analysis.drawdown_worst),
+            ] {
+                let py_metric = PyDict::new(py);
+                py_metric.set_item("mean", metric.mean)?;
+                py_metric.set_item("std", metric.std)?;
+                py_metric.set_item("p5", metric.p5)?;
+                py_mc.set_item(name, py_metric)?;
+            }
+            py_analysis.set_item("monte_carlo", py_mc)?;
+        }
 
         // Convert fills to a 2D array with mixed types
         let mut py_fills = Array2::from_elem((fills.len(), 10), py.None());
@@ -148,7 +184,19 @@ fn backtest_params_from_dict(dict: &PyDict) -> PyResult<BacktestParams> {
     Ok(BacktestParams {
         starting_balance: extract_value(dict, "starting_balance").unwrap_or_default(),
         maker_fee: extract_value(dict, "maker_fee").unwrap_or_default(),
+        taker_fee: extract_value(dict, "taker_fee").unwrap_or_default(),
         symbols: extract_value(dict, "symbols").unwrap_or_default(),
+        funding_interval: {
+            let funding_interval_float: f64 =
+                extract_value(dict, "funding_interval").unwrap_or_default();
+            funding_interval_float.round() as usize
+        },
+        mc_runs: {
+            let mc_runs_float: f64 = extract_value(dict, "mc_runs").unwrap_or_default();
+            mc_runs_float.round() as usize
+        },
+        mc_price_jitter_pct: extract_value(dict, "mc_price_jitter_pct").unwrap_or_default(),
+        mc_qty_jitter_pct: extract_value(dict, "mc_qty_jitter_pct").unwrap_or_default(),
     })
 }
 
@@ -159,6 +207,8 @@ fn exchange_params_from_dict(dict: &PyDict) -> PyResult<ExchangeParams> {
         min_qty: extract_value(dict, "min_qty").unwrap_or_default(),
         min_cost: extract_value(dict, "min_cost").unwrap_or_default(),
         c_mult: extract_value(dict, "c_mult").unwrap_or_default(),
+        maker_fee: extract_value(dict, "maker_fee").unwrap_or_default(),
+        taker_fee: extract_value(dict, "taker_fee").unwrap_or_default(),
     })
 }
 
@@ -174,6 +224,36 @@ fn bot_params_from_dict(dict: &PyDict) -> PyResult<BotParams> {
         close_grid_markup_range: extract_value(dict, "close_grid_markup_range")?,
         close_grid_min_markup: extract_value(dict, "close_grid_min_markup")?,
         close_grid_qty_pct: extract_value(dict, "close_grid_qty_pct")?,
+        close_grid_twap_dist: extract_value(dict, "close_grid_twap_dist").unwrap_or_default(),
+        close_grid_twap_weight: extract_value(dict, "close_grid_twap_weight").unwrap_or_default(),
+        close_stop_loss_pct: extract_value(dict, "close_stop_loss_pct").unwrap_or_default(),
+        close_stop_loss_qty_pct: extract_value(dict, "close_stop_loss_qty_pct")
+            .unwrap_or_default(),
+        close_bracket_mode: extract_value(dict, "close_bracket_mode").unwrap_or_default(),
+        close_auction_start_markup: extract_value(dict, "close_auction_start_markup")
+            .unwrap_or_default(),
+        close_auction_end_markup: extract_value(dict, "close_auction_end_markup")
+            .unwrap_or_default(),
+        close_auction_duration_candles: {
+            let close_auction_duration_candles_float: f64 =
+                extract_value(dict, "close_auction_duration_candles").unwrap_or_default();
+            close_auction_duration_candles_float.round() as usize
+        },
+        entry_execution_policy: {
+            let entry_execution_policy_str: String =
+                extract_value(dict, "entry_execution_policy").unwrap_or_default();
+            entry_execution_policy_str.parse().unwrap_or_default()
+        },
+        close_execution_policy: {
+            let close_execution_policy_str: String =
+                extract_value(dict, "close_execution_policy").unwrap_or_default();
+            close_execution_policy_str.parse().unwrap_or_default()
+        },
+        self_trade_behavior: {
+            let self_trade_behavior_str: String =
+                extract_value(dict, "self_trade_behavior").unwrap_or_default();
+            self_trade_behavior_str.parse().unwrap_or_default()
+        },
         close_trailing_retracement_pct: extract_value(dict, "close_trailing_retracement_pct")?,
         close_trailing_grid_ratio: extract_value(dict, "close_trailing_grid_ratio")?,
         close_trailing_qty_pct: extract_value(dict, "close_trailing_qty_pct")?,
@@ -182,7 +262,16 @@ fn bot_params_from_dict(dict: &PyDict) -> PyResult<BotParams> {
         entry_grid_spacing_weight: extract_value(dict, "entry_grid_spacing_weight")?,
         entry_grid_spacing_pct: extract_value(dict, "entry_grid_spacing_pct")?,
         entry_initial_ema_dist: extract_value(dict, "entry_initial_ema_dist")?,
+        entry_initial_twap_dist: extract_value(dict, "entry_initial_twap_dist").unwrap_or_default(),
+        entry_initial_twap_weight: extract_value(dict, "entry_initial_twap_weight")
+            .unwrap_or_default(),
         entry_initial_qty_pct: extract_value(dict, "entry_initial_qty_pct")?,
+        entry_qty_vol_scaling: extract_value(dict, "entry_qty_vol_scaling").unwrap_or_default(),
+        entry_qty_vol_window: {
+            let entry_qty_vol_window_float: f64 =
+                extract_value(dict, "entry_qty_vol_window").unwrap_or_default();
+            entry_qty_vol_window_float.round() as usize
+        },
         entry_trailing_retracement_pct: extract_value(dict, "entry_trailing_retracement_pct")?,
         entry_trailing_grid_ratio: extract_value(dict, "entry_trailing_grid_ratio")?,
         entry_trailing_threshold_pct: extract_value(dict, "entry_trailing_threshold_pct")?,
@@ -217,14 +306,21 @@ pub fn calc_grid_close_long_py(
     min_qty: f64,
     min_cost: f64,
     c_mult: f64,
+    maker_fee: f64,
+    taker_fee: f64,
     close_grid_markup_range: f64,
     close_grid_min_markup: f64,
     close_grid_qty_pct: f64,
+    close_grid_twap_dist: f64,
+    close_grid_twap_weight: f64,
+    close_execution_policy: String,
     wallet_exposure_limit: f64,
     balance: f64,
     position_size: f64,
     position_price: f64,
+    order_book_bid: f64,
     order_book_ask: f64,
+    twap_upper: f64,
 ) -> (f64, f64, String) {
     let exchange_params = ExchangeParams {
         qty_step,
@@ -232,11 +328,17 @@ pub fn calc_grid_close_long_py(
         min_qty,
         min_cost,
         c_mult,
+        maker_fee,
+        taker_fee,
     };
     let state_params = StateParams {
         balance,
         order_book: OrderBook {
+            bid: order_book_bid,
             ask: order_book_ask,
+        },
+        twap_bands: TwapBands {
+            upper: twap_upper,
             ..Default::default()
         },
         ..Default::default()
@@ -245,6 +347,9 @@ pub fn calc_grid_close_long_py(
         close_grid_markup_range,
         close_grid_min_markup,
         close_grid_qty_pct,
+        close_grid_twap_dist,
+        close_grid_twap_weight,
+        close_execution_policy: close_execution_policy.parse().unwrap_or_default(),
         wallet_exposure_limit,
         ..Default::default()
     };
@@ -257,6 +362,48 @@ pub fn calc_grid_close_long_py(
     (order.qty, order.price, order.order_type.to_string())
 }
 
+#[pyfunction]
+pub fn calc_stop_close_long_py(
+    qty_step: f64,
+    price_step: f64,
+    min_qty: f64,
+    min_cost: f64,
+    c_mult: f64,
+    close_stop_loss_pct: f64,
+    close_stop_loss_qty_pct: f64,
+    order_book_ask: f64,
+    position_size: f64,
+    position_price: f64,
+) -> (f64, f64, String) {
+    let exchange_params = ExchangeParams {
+        qty_step,
+        price_step,
+        min_qty,
+        min_cost,
+        c_mult,
+        ..Default::default()
+    };
+    let state_params = StateParams {
+        order_book: OrderBook {
+            ask: order_book_ask,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let bot_params = BotParams {
+        close_stop_loss_pct,
+        close_stop_loss_qty_pct,
+        ..Default::default()
+    };
+    let position = Position {
+        size: position_size,
+        price: position_price,
+    };
+
+    let order = calc_stop_close_long(&exchange_params, &state_params, &bot_params, &position);
+    (order.qty, order.price, order.order_type.to_string())
+}
+
 #[pyfunction]
 pub fn calc_trailing_close_long_py(
     price_step: f64,
@@ -310,14 +457,25 @@ pub fn calc_grid_entry_long_py(
     min_qty: f64,
     min_cost: f64,
     c_mult: f64,
+    maker_fee: f64,
+    taker_fee: f64,
     balance: f64,
     order_book_bid: f64,
+    order_book_ask: f64,
     ema_bands_lower: f64,
+    twap_lower: f64,
     entry_grid_double_down_factor: f64,
     entry_grid_spacing_weight: f64,
     entry_grid_spacing_pct: f64,
     entry_initial_ema_dist: f64,
+    entry_initial_twap_dist: f64,
+    entry_initial_twap_weight: f64,
     entry_initial_qty_pct: f64,
+    entry_qty_vol_scaling: f64,
+    noisiness_now: f64,
+    noisiness_median: f64,
+    entry_execution_policy: String,
+    self_trade_behavior: String,
     wallet_exposure_limit: f64,
     position_size: f64,
     position_price: f64,
@@ -328,24 +486,37 @@ pub fn calc_grid_entry_long_py(
         min_qty,
         min_cost,
         c_mult,
+        maker_fee,
+        taker_fee,
     };
     let state_params = StateParams {
         balance,
         order_book: OrderBook {
             bid: order_book_bid,
-            ..Default::default()
+            ask: order_book_ask,
         },
         ema_bands: EMABands {
             lower: ema_bands_lower,
             ..Default::default()
         },
+        twap_bands: TwapBands {
+            lower: twap_lower,
+            ..Default::default()
+        },
+        noisiness_now,
+        noisiness_median,
     };
     let bot_params = BotParams {
         entry_grid_double_down_factor,
         entry_grid_spacing_weight,
         entry_grid_spacing_pct,
         entry_initial_ema_dist,
+        entry_initial_twap_dist,
+        entry_initial_twap_weight,
         entry_initial_qty_pct,
+        entry_qty_vol_scaling,
+        entry_execution_policy: entry_execution_policy.parse().unwrap_or_default(),
+        self_trade_behavior: self_trade_behavior.parse().unwrap_or_default(),
         wallet_exposure_limit,
         ..Default::default()
     };
@@ -383,6 +554,7 @@ pub fn calc_trailing_entry_long_py(
         min_qty,
         min_cost,
         c_mult,
+        ..Default::default()
     };
     let state_params = StateParams {
         balance,
@@ -430,6 +602,8 @@ pub fn calc_next_entry_long_py(
     entry_grid_spacing_weight: f64,
     entry_grid_spacing_pct: f64,
     entry_initial_ema_dist: f64,
+    entry_initial_twap_dist: f64,
+    entry_initial_twap_weight: f64,
     entry_initial_qty_pct: f64,
     entry_trailing_grid_ratio: f64,
     entry_trailing_retracement_pct: f64,
@@ -441,6 +615,7 @@ pub fn calc_next_entry_long_py(
     min_since_open: f64,
     max_since_min: f64,
     ema_bands_lower: f64,
+    twap_lower: f64,
     order_book_bid: f64,
 ) -> (f64, f64, String) {
     let exchange_params = ExchangeParams {
@@ -449,6 +624,7 @@ pub fn calc_next_entry_long_py(
         min_qty,
         min_cost,
         c_mult,
+        ..Default::default()
     };
     let state_params = StateParams {
         balance,
@@ -460,6 +636,10 @@ pub fn calc_next_entry_long_py(
             lower: ema_bands_lower,
             ..Default::default()
         },
+        twap_bands: TwapBands {
+            lower: twap_lower,
+            ..Default::default()
+        },
         ..Default::default()
     };
     let bot_params = BotParams {
@@ -467,6 +647,8 @@ pub fn calc_next_entry_long_py(
         entry_grid_spacing_weight,
         entry_grid_spacing_pct,
         entry_initial_ema_dist,
+        entry_initial_twap_dist,
+        entry_initial_twap_weight,
         entry_initial_qty_pct,
         entry_trailing_grid_ratio,
         entry_trailing_retracement_pct,
@@ -508,10 +690,18 @@ pub fn calc_next_close_long_py(
     close_grid_markup_range: f64,
     close_grid_min_markup: f64,
     close_grid_qty_pct: f64,
+    close_stop_loss_pct: f64,
+    close_stop_loss_qty_pct: f64,
+    close_bracket_mode: bool,
+    close_grid_twap_dist: f64,
+    close_grid_twap_weight: f64,
     close_trailing_grid_ratio: f64,
     close_trailing_qty_pct: f64,
     close_trailing_retracement_pct: f64,
     close_trailing_threshold_pct: f64,
+    close_auction_start_markup: f64,
+    close_auction_end_markup: f64,
+    close_auction_duration_candles: f64,
     wallet_exposure_limit: f64,
     balance: f64,
     position_size: f64,
@@ -519,6 +709,8 @@ pub fn calc_next_close_long_py(
     max_since_open: f64,
     min_since_max: f64,
     order_book_ask: f64,
+    twap_upper: f64,
+    candles_since_open: f64,
 ) -> (f64, f64, String) {
     let exchange_params = ExchangeParams {
         qty_step,
@@ -526,6 +718,7 @@ pub fn calc_next_close_long_py(
         min_qty,
         min_cost,
         c_mult,
+        ..Default::default()
     };
     let state_params = StateParams {
         balance,
@@ -533,16 +726,28 @@ pub fn calc_next_close_long_py(
             ask: order_book_ask,
             ..Default::default()
         },
+        twap_bands: TwapBands {
+            upper: twap_upper,
+            ..Default::default()
+        },
         ..Default::default()
     };
     let bot_params = BotParams {
         close_grid_markup_range,
         close_grid_min_markup,
         close_grid_qty_pct,
+        close_stop_loss_pct,
+        close_stop_loss_qty_pct,
+        close_bracket_mode,
+        close_grid_twap_dist,
+        close_grid_twap_weight,
         close_trailing_grid_ratio,
         close_trailing_qty_pct,
         close_trailing_retracement_pct,
         close_trailing_threshold_pct,
+        close_auction_start_markup,
+        close_auction_end_markup,
+        close_auction_duration_candles: close_auction_duration_candles.round() as usize,
         wallet_exposure_limit,
         ..Default::default()
     };
@@ -561,6 +766,7 @@ pub fn calc_next_close_long_py(
         &bot_params,
         &position,
         &trailing_price_bundle,
+        candles_since_open.round() as usize,
     );
     (
         next_entry.qty,
@@ -569,6 +775,130 @@ pub fn calc_next_close_long_py(
     )
 }
 
+/// Returns both legs of the OCA bracket (take-profit, then stop-loss) so a live caller can place
+/// a genuine bracket pair on the exchange instead of just the nearer of the two.
+#[pyfunction]
+pub fn calc_bracket_close_long_py(
+    qty_step: f64,
+    price_step: f64,
+    min_qty: f64,
+    min_cost: f64,
+    c_mult: f64,
+    close_grid_markup_range: f64,
+    close_grid_min_markup: f64,
+    close_grid_qty_pct: f64,
+    close_stop_loss_pct: f64,
+    close_stop_loss_qty_pct: f64,
+    wallet_exposure_limit: f64,
+    balance: f64,
+    position_size: f64,
+    position_price: f64,
+    order_book_ask: f64,
+) -> ((f64, f64, String), (f64, f64, String)) {
+    let exchange_params = ExchangeParams {
+        qty_step,
+        price_step,
+        min_qty,
+        min_cost,
+        c_mult,
+        ..Default::default()
+    };
+    let state_params = StateParams {
+        balance,
+        order_book: OrderBook {
+            ask: order_book_ask,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let bot_params = BotParams {
+        close_grid_markup_range,
+        close_grid_min_markup,
+        close_grid_qty_pct,
+        close_stop_loss_pct,
+        close_stop_loss_qty_pct,
+        wallet_exposure_limit,
+        ..Default::default()
+    };
+    let position = Position {
+        size: position_size,
+        price: position_price,
+    };
+
+    let (tp_order, stop_order) =
+        calc_bracket_close_long(&exchange_params, &state_params, &bot_params, &position);
+    (
+        (tp_order.qty, tp_order.price, tp_order.order_type.to_string()),
+        (
+            stop_order.qty,
+            stop_order.price,
+            stop_order.order_type.to_string(),
+        ),
+    )
+}
+
+/// Mirror of [`calc_bracket_close_long_py`] for short positions.
+#[pyfunction]
+pub fn calc_bracket_close_short_py(
+    qty_step: f64,
+    price_step: f64,
+    min_qty: f64,
+    min_cost: f64,
+    c_mult: f64,
+    close_grid_markup_range: f64,
+    close_grid_min_markup: f64,
+    close_grid_qty_pct: f64,
+    close_stop_loss_pct: f64,
+    close_stop_loss_qty_pct: f64,
+    wallet_exposure_limit: f64,
+    balance: f64,
+    position_size: f64,
+    position_price: f64,
+    order_book_bid: f64,
+    order_book_ask: f64,
+) -> ((f64, f64, String), (f64, f64, String)) {
+    let exchange_params = ExchangeParams {
+        qty_step,
+        price_step,
+        min_qty,
+        min_cost,
+        c_mult,
+        ..Default::default()
+    };
+    let state_params = StateParams {
+        balance,
+        order_book: OrderBook {
+            bid: order_book_bid,
+            ask: order_book_ask,
+        },
+        ..Default::default()
+    };
+    let bot_params = BotParams {
+        close_grid_markup_range,
+        close_grid_min_markup,
+        close_grid_qty_pct,
+        close_stop_loss_pct,
+        close_stop_loss_qty_pct,
+        wallet_exposure_limit,
+        ..Default::default()
+    };
+    let position = Position {
+        size: position_size,
+        price: position_price,
+    };
+
+    let (tp_order, stop_order) =
+        calc_bracket_close_short(&exchange_params, &state_params, &bot_params, &position);
+    (
+        (tp_order.qty, tp_order.price, tp_order.order_type.to_string()),
+        (
+            stop_order.qty,
+            stop_order.price,
+            stop_order.order_type.to_string(),
+        ),
+    )
+}
+
 #[pyfunction]
 pub fn calc_next_entry_short_py(
     qty_step: f64,
@@ -580,6 +910,8 @@ pub fn calc_next_entry_short_py(
     entry_grid_spacing_weight: f64,
     entry_grid_spacing_pct: f64,
     entry_initial_ema_dist: f64,
+    entry_initial_twap_dist: f64,
+    entry_initial_twap_weight: f64,
     entry_initial_qty_pct: f64,
     entry_trailing_grid_ratio: f64,
     entry_trailing_retracement_pct: f64,
@@ -591,6 +923,7 @@ pub fn calc_next_entry_short_py(
     max_since_open: f64,
     min_since_max: f64,
     ema_bands_upper: f64,
+    twap_upper: f64,
     order_book_ask: f64,
 ) -> (f64, f64, String) {
     let exchange_params = ExchangeParams {
@@ -599,6 +932,7 @@ pub fn calc_next_entry_short_py(
         min_qty,
         min_cost,
         c_mult,
+        ..Default::default()
     };
     let state_params = StateParams {
         balance,
@@ -610,6 +944,10 @@ pub fn calc_next_entry_short_py(
             upper: ema_bands_upper,
             ..Default::default()
         },
+        twap_bands: TwapBands {
+            upper: twap_upper,
+            ..Default::default()
+        },
         ..Default::default()
     };
     let bot_params = BotParams {
@@ -617,6 +955,8 @@ pub fn calc_next_entry_short_py(
         entry_grid_spacing_weight,
         entry_grid_spacing_pct,
         entry_initial_ema_dist,
+        entry_initial_twap_dist,
+        entry_initial_twap_weight,
         entry_initial_qty_pct,
         entry_trailing_grid_ratio,
         entry_trailing_retracement_pct,
@@ -658,10 +998,18 @@ pub fn calc_next_close_short_py(
     close_grid_markup_range: f64,
     close_grid_min_markup: f64,
     close_grid_qty_pct: f64,
+    close_stop_loss_pct: f64,
+    close_stop_loss_qty_pct: f64,
+    close_bracket_mode: bool,
+    close_grid_twap_dist: f64,
+    close_grid_twap_weight: f64,
     close_trailing_grid_ratio: f64,
     close_trailing_qty_pct: f64,
     close_trailing_retracement_pct: f64,
     close_trailing_threshold_pct: f64,
+    close_auction_start_markup: f64,
+    close_auction_end_markup: f64,
+    close_auction_duration_candles: f64,
     wallet_exposure_limit: f64,
     balance: f64,
     position_size: f64,
@@ -669,6 +1017,8 @@ pub fn calc_next_close_short_py(
     min_since_open: f64,
     max_since_min: f64,
     order_book_bid: f64,
+    twap_lower: f64,
+    candles_since_open: f64,
 ) -> (f64, f64, String) {
     let exchange_params = ExchangeParams {
         qty_step,
@@ -676,6 +1026,7 @@ pub fn calc_next_close_short_py(
         min_qty,
         min_cost,
         c_mult,
+        ..Default::default()
     };
     let state_params = StateParams {
         balance,
@@ -683,16 +1034,28 @@ pub fn calc_next_close_short_py(
             bid: order_book_bid,
             ..Default::default()
         },
+        twap_bands: TwapBands {
+            lower: twap_lower,
+            ..Default::default()
+        },
         ..Default::default()
     };
     let bot_params = BotParams {
         close_grid_markup_range,
         close_grid_min_markup,
         close_grid_qty_pct,
+        close_stop_loss_pct,
+        close_stop_loss_qty_pct,
+        close_bracket_mode,
+        close_grid_twap_dist,
+        close_grid_twap_weight,
         close_trailing_grid_ratio,
         close_trailing_qty_pct,
         close_trailing_retracement_pct,
         close_trailing_threshold_pct,
+        close_auction_start_markup,
+        close_auction_end_markup,
+        close_auction_duration_candles: close_auction_duration_candles.round() as usize,
         wallet_exposure_limit,
         ..Default::default()
     };
@@ -711,6 +1074,7 @@ pub fn calc_next_close_short_py(
         &bot_params,
         &position,
         &trailing_price_bundle,
+        candles_since_open.round() as usize,
     );
     (
         next_entry.qty,
@@ -726,14 +1090,23 @@ pub fn calc_entries_long_py(
     min_qty: f64,
     min_cost: f64,
     c_mult: f64,
+    maker_fee: f64,
+    taker_fee: f64,
     entry_grid_double_down_factor: f64,
     entry_grid_spacing_weight: f64,
     entry_grid_spacing_pct: f64,
     entry_initial_ema_dist: f64,
+    entry_initial_twap_dist: f64,
+    entry_initial_twap_weight: f64,
     entry_initial_qty_pct: f64,
+    entry_qty_vol_scaling: f64,
+    noisiness_now: f64,
+    noisiness_median: f64,
     entry_trailing_grid_ratio: f64,
     entry_trailing_retracement_pct: f64,
     entry_trailing_threshold_pct: f64,
+    entry_execution_policy: String,
+    self_trade_behavior: String,
     wallet_exposure_limit: f64,
     balance: f64,
     position_size: f64,
@@ -741,7 +1114,9 @@ pub fn calc_entries_long_py(
     min_since_open: f64,
     max_since_min: f64,
     ema_bands_lower: f64,
+    twap_lower: f64,
     order_book_bid: f64,
+    order_book_ask: f64,
 ) -> Vec<(f64, f64, String)> {
     let exchange_params = ExchangeParams {
         qty_step,
@@ -749,19 +1124,26 @@ pub fn calc_entries_long_py(
         min_qty,
         min_cost,
         c_mult,
+        maker_fee,
+        taker_fee,
     };
 
     let state_params = StateParams {
         balance,
         order_book: OrderBook {
             bid: order_book_bid,
-            ..Default::default()
+            ask: order_book_ask,
         },
         ema_bands: EMABands {
             lower: ema_bands_lower,
             ..Default::default()
         },
-        ..Default::default()
+        twap_bands: TwapBands {
+            lower: twap_lower,
+            ..Default::default()
+        },
+        noisiness_now,
+        noisiness_median,
     };
 
     let bot_params = BotParams {
@@ -769,10 +1151,15 @@ pub fn calc_entries_long_py(
         entry_grid_spacing_weight,
         entry_grid_spacing_pct,
         entry_initial_ema_dist,
+        entry_initial_twap_dist,
+        entry_initial_twap_weight,
         entry_initial_qty_pct,
+        entry_qty_vol_scaling,
         entry_trailing_grid_ratio,
         entry_trailing_retracement_pct,
         entry_trailing_threshold_pct,
+        entry_execution_policy: entry_execution_policy.parse().unwrap_or_default(),
+        self_trade_behavior: self_trade_behavior.parse().unwrap_or_default(),
         wallet_exposure_limit,
         ..Default::default()
     };
@@ -808,14 +1195,23 @@ pub fn calc_entries_short_py(
     min_qty: f64,
     min_cost: f64,
     c_mult: f64,
+    maker_fee: f64,
+    taker_fee: f64,
     entry_grid_double_down_factor: f64,
     entry_grid_spacing_weight: f64,
     entry_grid_spacing_pct: f64,
     entry_initial_ema_dist: f64,
+    entry_initial_twap_dist: f64,
+    entry_initial_twap_weight: f64,
     entry_initial_qty_pct: f64,
+    entry_qty_vol_scaling: f64,
+    noisiness_now: f64,
+    noisiness_median: f64,
     entry_trailing_grid_ratio: f64,
     entry_trailing_retracement_pct: f64,
     entry_trailing_threshold_pct: f64,
+    entry_execution_policy: String,
+    self_trade_behavior: String,
     wallet_exposure_limit: f64,
     balance: f64,
     position_size: f64,
@@ -823,6 +1219,8 @@ pub fn calc_entries_short_py(
     max_since_open: f64,
     min_since_max: f64,
     ema_bands_upper: f64,
+    twap_upper: f64,
+    order_book_bid: f64,
     order_book_ask: f64,
 ) -> Vec<(f64, f64, String)> {
     let exchange_params = ExchangeParams {
@@ -831,19 +1229,26 @@ pub fn calc_entries_short_py(
         min_qty,
         min_cost,
         c_mult,
+        maker_fee,
+        taker_fee,
     };
 
     let state_params = StateParams {
         balance,
         order_book: OrderBook {
+            bid: order_book_bid,
             ask: order_book_ask,
-            ..Default::default()
         },
         ema_bands: EMABands {
             upper: ema_bands_upper,
             ..Default::default()
         },
-        ..Default::default()
+        twap_bands: TwapBands {
+            upper: twap_upper,
+            ..Default::default()
+        },
+        noisiness_now,
+        noisiness_median,
     };
 
     let bot_params = BotParams {
@@ -851,10 +1256,15 @@ pub fn calc_entries_short_py(
         entry_grid_spacing_weight,
         entry_grid_spacing_pct,
         entry_initial_ema_dist,
+        entry_initial_twap_dist,
+        entry_initial_twap_weight,
         entry_initial_qty_pct,
+        entry_qty_vol_scaling,
         entry_trailing_grid_ratio,
         entry_trailing_retracement_pct,
         entry_trailing_threshold_pct,
+        entry_execution_policy: entry_execution_policy.parse().unwrap_or_default(),
+        self_trade_behavior: self_trade_behavior.parse().unwrap_or_default(),
         wallet_exposure_limit,
         ..Default::default()
     };
@@ -890,20 +1300,34 @@ pub fn calc_closes_long_py(
     min_qty: f64,
     min_cost: f64,
     c_mult: f64,
+    maker_fee: f64,
+    taker_fee: f64,
     close_grid_markup_range: f64,
     close_grid_min_markup: f64,
     close_grid_qty_pct: f64,
+    close_stop_loss_pct: f64,
+    close_stop_loss_qty_pct: f64,
+    close_bracket_mode: bool,
+    close_grid_twap_dist: f64,
+    close_grid_twap_weight: f64,
     close_trailing_grid_ratio: f64,
     close_trailing_qty_pct: f64,
     close_trailing_retracement_pct: f64,
     close_trailing_threshold_pct: f64,
+    close_auction_start_markup: f64,
+    close_auction_end_markup: f64,
+    close_auction_duration_candles: f64,
+    close_execution_policy: String,
     wallet_exposure_limit: f64,
     balance: f64,
     position_size: f64,
     position_price: f64,
     max_since_open: f64,
     min_since_max: f64,
+    order_book_bid: f64,
     order_book_ask: f64,
+    twap_upper: f64,
+    candles_since_open: f64,
 ) -> Vec<(f64, f64, String)> {
     let exchange_params = ExchangeParams {
         qty_step,
@@ -911,12 +1335,18 @@ pub fn calc_closes_long_py(
         min_qty,
         min_cost,
         c_mult,
+        maker_fee,
+        taker_fee,
     };
 
     let state_params = StateParams {
         balance,
         order_book: OrderBook {
+            bid: order_book_bid,
             ask: order_book_ask,
+        },
+        twap_bands: TwapBands {
+            upper: twap_upper,
             ..Default::default()
         },
         ..Default::default()
@@ -926,10 +1356,19 @@ pub fn calc_closes_long_py(
         close_grid_markup_range,
         close_grid_min_markup,
         close_grid_qty_pct,
+        close_stop_loss_pct,
+        close_stop_loss_qty_pct,
+        close_bracket_mode,
+        close_grid_twap_dist,
+        close_grid_twap_weight,
         close_trailing_grid_ratio,
         close_trailing_qty_pct,
         close_trailing_retracement_pct,
         close_trailing_threshold_pct,
+        close_auction_start_markup,
+        close_auction_end_markup,
+        close_auction_duration_candles: close_auction_duration_candles.round() as usize,
+        close_execution_policy: close_execution_policy.parse().unwrap_or_default(),
         wallet_exposure_limit,
         ..Default::default()
     };
@@ -949,6 +1388,7 @@ pub fn calc_closes_long_py(
         &bot_params,
         &position,
         &trailing_price_bundle,
+        candles_since_open.round() as usize,
     );
 
     // Convert closes to Python-compatible format
@@ -965,13 +1405,24 @@ pub fn calc_closes_short_py(
     min_qty: f64,
     min_cost: f64,
     c_mult: f64,
+    maker_fee: f64,
+    taker_fee: f64,
     close_grid_markup_range: f64,
     close_grid_min_markup: f64,
     close_grid_qty_pct: f64,
+    close_stop_loss_pct: f64,
+    close_stop_loss_qty_pct: f64,
+    close_bracket_mode: bool,
+    close_grid_twap_dist: f64,
+    close_grid_twap_weight: f64,
     close_trailing_grid_ratio: f64,
     close_trailing_qty_pct: f64,
     close_trailing_retracement_pct: f64,
     close_trailing_threshold_pct: f64,
+    close_auction_start_markup: f64,
+    close_auction_end_markup: f64,
+    close_auction_duration_candles: f64,
+    close_execution_policy: String,
     wallet_exposure_limit: f64,
     balance: f64,
     position_size: f64,
@@ -979,6 +1430,9 @@ pub fn calc_closes_short_py(
     min_since_open: f64,
     max_since_min: f64,
     order_book_bid: f64,
+    order_book_ask: f64,
+    twap_lower: f64,
+    candles_since_open: f64,
 ) -> Vec<(f64, f64, String)> {
     let exchange_params = ExchangeParams {
         qty_step,
@@ -986,12 +1440,18 @@ pub fn calc_closes_short_py(
         min_qty,
         min_cost,
         c_mult,
+        maker_fee,
+        taker_fee,
     };
 
     let state_params = StateParams {
         balance,
         order_book: OrderBook {
             bid: order_book_bid,
+            ask: order_book_ask,
+        },
+        twap_bands: TwapBands {
+            lower: twap_lower,
             ..Default::default()
         },
         ..Default::default()
@@ -1001,10 +1461,19 @@ pub fn calc_closes_short_py(
         close_grid_markup_range,
         close_grid_min_markup,
         close_grid_qty_pct,
+        close_stop_loss_pct,
+        close_stop_loss_qty_pct,
+        close_bracket_mode,
+        close_grid_twap_dist,
+        close_grid_twap_weight,
         close_trailing_grid_ratio,
         close_trailing_qty_pct,
         close_trailing_retracement_pct,
         close_trailing_threshold_pct,
+        close_auction_start_markup,
+        close_auction_end_markup,
+        close_auction_duration_candles: close_auction_duration_candles.round() as usize,
+        close_execution_policy: close_execution_policy.parse().unwrap_or_default(),
         wallet_exposure_limit,
         ..Default::default()
     };
@@ -1023,6 +1492,478 @@ pub fn calc_closes_short_py(
         &bot_params,
         &position,
         &trailing_price_bundle,
+        candles_since_open.round() as usize,
+    );
+
+    // Convert closes to Python-compatible format
+    closes
+        .into_iter()
+        .map(|order| (order.qty, order.price, order.order_type.to_string()))
+        .collect()
+}
+
+#[pyfunction]
+pub fn recalc_entries_after_partial_fill_long_py(
+    qty_step: f64,
+    price_step: f64,
+    min_qty: f64,
+    min_cost: f64,
+    c_mult: f64,
+    maker_fee: f64,
+    taker_fee: f64,
+    entry_grid_double_down_factor: f64,
+    entry_grid_spacing_weight: f64,
+    entry_grid_spacing_pct: f64,
+    entry_initial_ema_dist: f64,
+    entry_initial_twap_dist: f64,
+    entry_initial_twap_weight: f64,
+    entry_initial_qty_pct: f64,
+    entry_qty_vol_scaling: f64,
+    noisiness_now: f64,
+    noisiness_median: f64,
+    entry_trailing_grid_ratio: f64,
+    entry_trailing_retracement_pct: f64,
+    entry_trailing_threshold_pct: f64,
+    entry_execution_policy: String,
+    self_trade_behavior: String,
+    wallet_exposure_limit: f64,
+    balance: f64,
+    position_size: f64,
+    position_price: f64,
+    min_since_open: f64,
+    max_since_min: f64,
+    ema_bands_lower: f64,
+    twap_lower: f64,
+    order_book_bid: f64,
+    order_book_ask: f64,
+    plan_qtys: Vec<f64>,
+    plan_prices: Vec<f64>,
+    filled_qty: f64,
+    filled_price: f64,
+) -> Vec<(f64, f64, String)> {
+    let exchange_params = ExchangeParams {
+        qty_step,
+        price_step,
+        min_qty,
+        min_cost,
+        c_mult,
+        maker_fee,
+        taker_fee,
+    };
+
+    let state_params = StateParams {
+        balance,
+        order_book: OrderBook {
+            bid: order_book_bid,
+            ask: order_book_ask,
+        },
+        ema_bands: EMABands {
+            lower: ema_bands_lower,
+            ..Default::default()
+        },
+        twap_bands: TwapBands {
+            lower: twap_lower,
+            ..Default::default()
+        },
+        noisiness_now,
+        noisiness_median,
+    };
+
+    let bot_params = BotParams {
+        entry_grid_double_down_factor,
+        entry_grid_spacing_weight,
+        entry_grid_spacing_pct,
+        entry_initial_ema_dist,
+        entry_initial_twap_dist,
+        entry_initial_twap_weight,
+        entry_initial_qty_pct,
+        entry_qty_vol_scaling,
+        entry_trailing_grid_ratio,
+        entry_trailing_retracement_pct,
+        entry_trailing_threshold_pct,
+        entry_execution_policy: entry_execution_policy.parse().unwrap_or_default(),
+        self_trade_behavior: self_trade_behavior.parse().unwrap_or_default(),
+        wallet_exposure_limit,
+        ..Default::default()
+    };
+
+    let position = Position {
+        size: position_size,
+        price: position_price,
+    };
+    let trailing_price_bundle = TrailingPriceBundle {
+        min_since_open: min_since_open,
+        max_since_min: max_since_min,
+        ..Default::default()
+    };
+    let original_plan: Vec<Order> = plan_qtys
+        .into_iter()
+        .zip(plan_prices)
+        .map(|(qty, price)| Order {
+            qty,
+            price,
+            order_type: OrderType::EntryGridNormalLong,
+        })
+        .collect();
+    let entries = recalc_entries_after_partial_fill_long(
+        &exchange_params,
+        &state_params,
+        &bot_params,
+        &position,
+        &trailing_price_bundle,
+        &original_plan,
+        filled_qty,
+        filled_price,
+    );
+
+    // Convert entries to Python-compatible format
+    entries
+        .into_iter()
+        .map(|order| (order.qty, order.price, order.order_type.to_string()))
+        .collect()
+}
+
+#[pyfunction]
+pub fn recalc_entries_after_partial_fill_short_py(
+    qty_step: f64,
+    price_step: f64,
+    min_qty: f64,
+    min_cost: f64,
+    c_mult: f64,
+    maker_fee: f64,
+    taker_fee: f64,
+    entry_grid_double_down_factor: f64,
+    entry_grid_spacing_weight: f64,
+    entry_grid_spacing_pct: f64,
+    entry_initial_ema_dist: f64,
+    entry_initial_twap_dist: f64,
+    entry_initial_twap_weight: f64,
+    entry_initial_qty_pct: f64,
+    entry_qty_vol_scaling: f64,
+    noisiness_now: f64,
+    noisiness_median: f64,
+    entry_trailing_grid_ratio: f64,
+    entry_trailing_retracement_pct: f64,
+    entry_trailing_threshold_pct: f64,
+    entry_execution_policy: String,
+    self_trade_behavior: String,
+    wallet_exposure_limit: f64,
+    balance: f64,
+    position_size: f64,
+    position_price: f64,
+    max_since_open: f64,
+    min_since_max: f64,
+    ema_bands_upper: f64,
+    twap_upper: f64,
+    order_book_bid: f64,
+    order_book_ask: f64,
+    plan_qtys: Vec<f64>,
+    plan_prices: Vec<f64>,
+    filled_qty: f64,
+    filled_price: f64,
+) -> Vec<(f64, f64, String)> {
+    let exchange_params = ExchangeParams {
+        qty_step,
+        price_step,
+        min_qty,
+        min_cost,
+        c_mult,
+        maker_fee,
+        taker_fee,
+    };
+
+    let state_params = StateParams {
+        balance,
+        order_book: OrderBook {
+            bid: order_book_bid,
+            ask: order_book_ask,
+        },
+        ema_bands: EMABands {
+            upper: ema_bands_upper,
+            ..Default::default()
+        },
+        twap_bands: TwapBands {
+            upper: twap_upper,
+            ..Default::default()
+        },
+        noisiness_now,
+        noisiness_median,
+    };
+
+    let bot_params = BotParams {
+        entry_grid_double_down_factor,
+        entry_grid_spacing_weight,
+        entry_grid_spacing_pct,
+        entry_initial_ema_dist,
+        entry_initial_twap_dist,
+        entry_initial_twap_weight,
+        entry_initial_qty_pct,
+        entry_qty_vol_scaling,
+        entry_trailing_grid_ratio,
+        entry_trailing_retracement_pct,
+        entry_trailing_threshold_pct,
+        entry_execution_policy: entry_execution_policy.parse().unwrap_or_default(),
+        self_trade_behavior: self_trade_behavior.parse().unwrap_or_default(),
+        wallet_exposure_limit,
+        ..Default::default()
+    };
+
+    let position = Position {
+        size: position_size,
+        price: position_price,
+    };
+    let trailing_price_bundle = TrailingPriceBundle {
+        max_since_open: max_since_open,
+        min_since_max: min_since_max,
+        ..Default::default()
+    };
+    let original_plan: Vec<Order> = plan_qtys
+        .into_iter()
+        .zip(plan_prices)
+        .map(|(qty, price)| Order {
+            qty,
+            price,
+            order_type: OrderType::EntryGridNormalShort,
+        })
+        .collect();
+    let entries = recalc_entries_after_partial_fill_short(
+        &exchange_params,
+        &state_params,
+        &bot_params,
+        &position,
+        &trailing_price_bundle,
+        &original_plan,
+        filled_qty,
+        filled_price,
+    );
+
+    // Convert entries to Python-compatible format
+    entries
+        .into_iter()
+        .map(|order| (order.qty, order.price, order.order_type.to_string()))
+        .collect()
+}
+
+#[pyfunction]
+pub fn recalc_closes_after_partial_fill_long_py(
+    qty_step: f64,
+    price_step: f64,
+    min_qty: f64,
+    min_cost: f64,
+    c_mult: f64,
+    maker_fee: f64,
+    taker_fee: f64,
+    close_grid_markup_range: f64,
+    close_grid_min_markup: f64,
+    close_grid_qty_pct: f64,
+    close_grid_twap_dist: f64,
+    close_grid_twap_weight: f64,
+    close_trailing_grid_ratio: f64,
+    close_trailing_qty_pct: f64,
+    close_trailing_retracement_pct: f64,
+    close_trailing_threshold_pct: f64,
+    close_auction_start_markup: f64,
+    close_auction_end_markup: f64,
+    close_auction_duration_candles: f64,
+    close_execution_policy: String,
+    wallet_exposure_limit: f64,
+    balance: f64,
+    position_size: f64,
+    position_price: f64,
+    max_since_open: f64,
+    min_since_max: f64,
+    order_book_bid: f64,
+    order_book_ask: f64,
+    twap_upper: f64,
+    candles_since_open: f64,
+    plan_qtys: Vec<f64>,
+    plan_prices: Vec<f64>,
+    filled_qty: f64,
+    filled_price: f64,
+) -> Vec<(f64, f64, String)> {
+    let exchange_params = ExchangeParams {
+        qty_step,
+        price_step,
+        min_qty,
+        min_cost,
+        c_mult,
+        maker_fee,
+        taker_fee,
+    };
+
+    let state_params = StateParams {
+        balance,
+        order_book: OrderBook {
+            bid: order_book_bid,
+            ask: order_book_ask,
+        },
+        twap_bands: TwapBands {
+            upper: twap_upper,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let bot_params = BotParams {
+        close_grid_markup_range,
+        close_grid_min_markup,
+        close_grid_qty_pct,
+        close_grid_twap_dist,
+        close_grid_twap_weight,
+        close_trailing_grid_ratio,
+        close_trailing_qty_pct,
+        close_trailing_retracement_pct,
+        close_trailing_threshold_pct,
+        close_auction_start_markup,
+        close_auction_end_markup,
+        close_auction_duration_candles: close_auction_duration_candles.round() as usize,
+        close_execution_policy: close_execution_policy.parse().unwrap_or_default(),
+        wallet_exposure_limit,
+        ..Default::default()
+    };
+
+    let position = Position {
+        size: position_size,
+        price: position_price,
+    };
+    let trailing_price_bundle = TrailingPriceBundle {
+        max_since_open: max_since_open,
+        min_since_max: min_since_max,
+        ..Default::default()
+    };
+    let original_plan: Vec<Order> = plan_qtys
+        .into_iter()
+        .zip(plan_prices)
+        .map(|(qty, price)| Order {
+            qty,
+            price,
+            order_type: OrderType::CloseGridLong,
+        })
+        .collect();
+    let closes = recalc_closes_after_partial_fill_long(
+        &exchange_params,
+        &state_params,
+        &bot_params,
+        &position,
+        &trailing_price_bundle,
+        candles_since_open.round() as usize,
+        &original_plan,
+        filled_qty,
+        filled_price,
+    );
+
+    // Convert closes to Python-compatible format
+    closes
+        .into_iter()
+        .map(|order| (order.qty, order.price, order.order_type.to_string()))
+        .collect()
+}
+
+#[pyfunction]
+pub fn recalc_closes_after_partial_fill_short_py(
+    qty_step: f64,
+    price_step: f64,
+    min_qty: f64,
+    min_cost: f64,
+    c_mult: f64,
+    maker_fee: f64,
+    taker_fee: f64,
+    close_grid_markup_range: f64,
+    close_grid_min_markup: f64,
+    close_grid_qty_pct: f64,
+    close_grid_twap_dist: f64,
+    close_grid_twap_weight: f64,
+    close_trailing_grid_ratio: f64,
+    close_trailing_qty_pct: f64,
+    close_trailing_retracement_pct: f64,
+    close_trailing_threshold_pct: f64,
+    close_auction_start_markup: f64,
+    close_auction_end_markup: f64,
+    close_auction_duration_candles: f64,
+    close_execution_policy: String,
+    wallet_exposure_limit: f64,
+    balance: f64,
+    position_size: f64,
+    position_price: f64,
+    min_since_open: f64,
+    max_since_min: f64,
+    order_book_bid: f64,
+    order_book_ask: f64,
+    twap_lower: f64,
+    candles_since_open: f64,
+    plan_qtys: Vec<f64>,
+    plan_prices: Vec<f64>,
+    filled_qty: f64,
+    filled_price: f64,
+) -> Vec<(f64, f64, String)> {
+    let exchange_params = ExchangeParams {
+        qty_step,
+        price_step,
+        min_qty,
+        min_cost,
+        c_mult,
+        maker_fee,
+        taker_fee,
+    };
+
+    let state_params = StateParams {
+        balance,
+        order_book: OrderBook {
+            bid: order_book_bid,
+            ask: order_book_ask,
+        },
+        twap_bands: TwapBands {
+            lower: twap_lower,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let bot_params = BotParams {
+        close_grid_markup_range,
+        close_grid_min_markup,
+        close_grid_qty_pct,
+        close_grid_twap_dist,
+        close_grid_twap_weight,
+        close_trailing_grid_ratio,
+        close_trailing_qty_pct,
+        close_trailing_retracement_pct,
+        close_trailing_threshold_pct,
+        close_auction_start_markup,
+        close_auction_end_markup,
+        close_auction_duration_candles: close_auction_duration_candles.round() as usize,
+        close_execution_policy: close_execution_policy.parse().unwrap_or_default(),
+        wallet_exposure_limit,
+        ..Default::default()
+    };
+    let position = Position {
+        size: position_size,
+        price: position_price,
+    };
+    let trailing_price_bundle = TrailingPriceBundle {
+        min_since_open: min_since_open,
+        max_since_min: max_since_min,
+        ..Default::default()
+    };
+    let original_plan: Vec<Order> = plan_qtys
+        .into_iter()
+        .zip(plan_prices)
+        .map(|(qty, price)| Order {
+            qty,
+            price,
+            order_type: OrderType::CloseGridShort,
+        })
+        .collect();
+    let closes = recalc_closes_after_partial_fill_short(
+        &exchange_params,
+        &state_params,
+        &bot_params,
+        &position,
+        &trailing_price_bundle,
+        candles_since_open.round() as usize,
+        &original_plan,
+        filled_qty,
+        filled_price,
     );
 
     // Convert closes to Python-compatible format
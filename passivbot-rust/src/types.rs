@@ -0,0 +1,359 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderType {
+    EntryInitialNormalLong,
+    EntryInitialPartialLong,
+    EntryGridNormalLong,
+    EntryGridInwardLong,
+    EntryTrailingNormalLong,
+    EntryTrailingCroppedLong,
+    CloseGridLong,
+    CloseTrailingLong,
+    CloseStopLossLong,
+    CloseAuctionLong,
+    CloseUnstuckLong,
+    EntryInitialNormalShort,
+    EntryInitialPartialShort,
+    EntryGridNormalShort,
+    EntryGridInwardShort,
+    EntryTrailingNormalShort,
+    EntryTrailingCroppedShort,
+    CloseGridShort,
+    CloseTrailingShort,
+    CloseStopLossShort,
+    CloseAuctionShort,
+    CloseUnstuckShort,
+    Funding,
+    Empty,
+}
+
+impl fmt::Display for OrderType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            OrderType::EntryInitialNormalLong => "entry_initial_normal_long",
+            OrderType::EntryInitialPartialLong => "entry_initial_partial_long",
+            OrderType::EntryGridNormalLong => "entry_grid_normal_long",
+            OrderType::EntryGridInwardLong => "entry_grid_inward_long",
+            OrderType::EntryTrailingNormalLong => "entry_trailing_normal_long",
+            OrderType::EntryTrailingCroppedLong => "entry_trailing_cropped_long",
+            OrderType::CloseGridLong => "close_grid_long",
+            OrderType::CloseTrailingLong => "close_trailing_long",
+            OrderType::CloseStopLossLong => "close_stop_loss_long",
+            OrderType::CloseAuctionLong => "close_auction_long",
+            OrderType::CloseUnstuckLong => "close_unstuck_long",
+            OrderType::EntryInitialNormalShort => "entry_initial_normal_short",
+            OrderType::EntryInitialPartialShort => "entry_initial_partial_short",
+            OrderType::EntryGridNormalShort => "entry_grid_normal_short",
+            OrderType::EntryGridInwardShort => "entry_grid_inward_short",
+            OrderType::EntryTrailingNormalShort => "entry_trailing_normal_short",
+            OrderType::EntryTrailingCroppedShort => "entry_trailing_cropped_short",
+            OrderType::CloseGridShort => "close_grid_short",
+            OrderType::CloseTrailingShort => "close_trailing_short",
+            OrderType::CloseStopLossShort => "close_stop_loss_short",
+            OrderType::CloseAuctionShort => "close_auction_short",
+            OrderType::CloseUnstuckShort => "close_unstuck_short",
+            OrderType::Funding => "funding",
+            OrderType::Empty => "empty",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Default for OrderType {
+    fn default() -> Self {
+        OrderType::Empty
+    }
+}
+
+impl OrderType {
+    /// Grid orders rest on the book and pay the maker fee; trailing entries/closes, the stop
+    /// loss, and the unstuck mechanism all cross the book on trigger and pay the taker fee.
+    /// Grid orders placed under `ExecutionPolicy::ImmediateOrCancel` also cross the book and
+    /// pay the taker fee; see [`Self::is_grid_order`], checked separately since that depends on
+    /// the execution policy in effect rather than the order type alone.
+    pub fn is_taker(&self) -> bool {
+        matches!(
+            self,
+            OrderType::EntryTrailingNormalLong
+                | OrderType::EntryTrailingCroppedLong
+                | OrderType::EntryTrailingNormalShort
+                | OrderType::EntryTrailingCroppedShort
+                | OrderType::CloseTrailingLong
+                | OrderType::CloseTrailingShort
+                | OrderType::CloseStopLossLong
+                | OrderType::CloseStopLossShort
+                | OrderType::CloseUnstuckLong
+                | OrderType::CloseUnstuckShort
+        )
+    }
+
+    /// True for order types priced via `apply_execution_policy_buy`/`_sell`, i.e. the ones whose
+    /// maker/taker fee depends on the configured `ExecutionPolicy` rather than being fixed by
+    /// `order_type` alone (initial/grid entries, grid closes, auction closes).
+    pub fn is_grid_order(&self) -> bool {
+        matches!(
+            self,
+            OrderType::EntryInitialNormalLong
+                | OrderType::EntryInitialPartialLong
+                | OrderType::EntryGridNormalLong
+                | OrderType::EntryGridInwardLong
+                | OrderType::EntryInitialNormalShort
+                | OrderType::EntryInitialPartialShort
+                | OrderType::EntryGridNormalShort
+                | OrderType::EntryGridInwardShort
+                | OrderType::CloseGridLong
+                | OrderType::CloseGridShort
+                | OrderType::CloseAuctionLong
+                | OrderType::CloseAuctionShort
+        )
+    }
+}
+
+/// How a grid order should behave relative to the book, modeled on Serum's `OrderType`
+/// (`Limit`, `PostOnly`, `ImmediateOrCancel`). Applied only to resting grid entries/closes;
+/// trailing entries/closes and the stop loss already cross the book intentionally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionPolicy {
+    /// Rest at the computed grid price as-is, taker or maker depending on where it lands.
+    Limit,
+    /// Clamp the price to the near side of the spread so the order always rests as maker.
+    PostOnly,
+    /// Force the price through the book so the order fills immediately, as taker.
+    ImmediateOrCancel,
+}
+
+impl Default for ExecutionPolicy {
+    fn default() -> Self {
+        ExecutionPolicy::Limit
+    }
+}
+
+impl std::str::FromStr for ExecutionPolicy {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "post_only" => Ok(ExecutionPolicy::PostOnly),
+            "immediate_or_cancel" => Ok(ExecutionPolicy::ImmediateOrCancel),
+            _ => Ok(ExecutionPolicy::Limit),
+        }
+    }
+}
+
+/// What to do when a freshly computed grid entry would cross the symbol's own resting grid
+/// close, modeled on Serum's self-trade behaviors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfTradeBehavior {
+    /// Let the crossing order through; it decrements against the resting order as normal.
+    DecrementTake,
+    /// Suppress the new (incoming) order so the resting one is left untouched.
+    CancelProvide,
+    /// Suppress the new order; since both legs are recomputed fresh from position state each
+    /// tick, the resting leg is naturally replaced next tick rather than double-filled.
+    CancelBoth,
+}
+
+impl Default for SelfTradeBehavior {
+    fn default() -> Self {
+        SelfTradeBehavior::DecrementTake
+    }
+}
+
+impl std::str::FromStr for SelfTradeBehavior {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cancel_provide" => Ok(SelfTradeBehavior::CancelProvide),
+            "cancel_both" => Ok(SelfTradeBehavior::CancelBoth),
+            _ => Ok(SelfTradeBehavior::DecrementTake),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Order {
+    pub qty: f64,
+    pub price: f64,
+    pub order_type: OrderType,
+}
+
+impl Order {
+    pub fn empty() -> Self {
+        Order {
+            qty: 0.0,
+            price: 0.0,
+            order_type: OrderType::Empty,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExchangeParams {
+    pub qty_step: f64,
+    pub price_step: f64,
+    pub min_qty: f64,
+    pub min_cost: f64,
+    pub c_mult: f64,
+    /// Fee paid (as a fraction of notional) when a grid order rests and is filled as maker.
+    /// Negative on rebate venues.
+    pub maker_fee: f64,
+    /// Fee paid (as a fraction of notional) when an order crosses the book and fills as taker.
+    pub taker_fee: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BacktestParams {
+    pub starting_balance: f64,
+    pub maker_fee: f64,
+    pub taker_fee: f64,
+    pub symbols: Vec<String>,
+    /// Number of candles between funding accruals. 0 disables funding simulation.
+    pub funding_interval: usize,
+    /// Number of Monte-Carlo jitter passes over the fills. 0 or 1 disables the feature and
+    /// `run_backtest` returns only the single deterministic analysis.
+    pub mc_runs: usize,
+    /// Fraction by which each fill's price is randomly perturbed (±) per Monte-Carlo run.
+    pub mc_price_jitter_pct: f64,
+    /// Fraction by which each fill's qty is randomly perturbed (±) per Monte-Carlo run.
+    pub mc_qty_jitter_pct: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EMABands {
+    pub upper: f64,
+    pub lower: f64,
+}
+
+/// Time-weighted average price band, computed upstream from a bucketed `(cumulative_price *
+/// elapsed, elapsed)` ring so `lower`/`upper` track `sum(price*dt)/sum(dt)` over a rolling
+/// window rather than a snapshot price. A manipulation-resistant alternative to [`EMABands`]
+/// for thin markets; zero when unset (no blending applied).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TwapBands {
+    pub upper: f64,
+    pub lower: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OrderBook {
+    pub bid: f64,
+    pub ask: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Position {
+    pub size: f64,
+    pub price: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TrailingPriceBundle {
+    pub min_since_open: f64,
+    pub max_since_min: f64,
+    pub max_since_open: f64,
+    pub min_since_max: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StateParams {
+    pub balance: f64,
+    pub order_book: OrderBook,
+    pub ema_bands: EMABands,
+    pub twap_bands: TwapBands,
+    /// Current value of the rolling `calc_noisiness` series for this symbol, used to scale
+    /// initial entry size inversely to recent volatility.
+    pub noisiness_now: f64,
+    /// Median of the rolling noisiness series for this symbol; the scaling reference point.
+    pub noisiness_median: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BotParams {
+    pub close_grid_markup_range: f64,
+    pub close_grid_min_markup: f64,
+    pub close_grid_qty_pct: f64,
+    /// When non-zero, the grid close price is blended with a price offset from
+    /// [`StateParams::twap_bands`]'s upper (long) / lower (short) edge by this fraction, instead
+    /// of relying solely on `close_grid_min_markup`/`close_grid_markup_range` against
+    /// `position.price`. The blend ratio is set by `close_grid_twap_weight`.
+    pub close_grid_twap_dist: f64,
+    /// Weight of the TWAP-offset price in the [`close_grid_twap_dist`](Self::close_grid_twap_dist)
+    /// blend, in `[0, 1]`: 0 keeps the markup-based price untouched, 1 uses the TWAP-offset price
+    /// outright, 0.5 is an equal-weight average. Has no effect while `close_grid_twap_dist` is 0.
+    pub close_grid_twap_weight: f64,
+    pub close_trailing_retracement_pct: f64,
+    pub close_trailing_grid_ratio: f64,
+    pub close_trailing_qty_pct: f64,
+    pub close_trailing_threshold_pct: f64,
+    pub close_stop_loss_pct: f64,
+    pub close_stop_loss_qty_pct: f64,
+    /// Starting markup of the Dutch-auction close decay. Whether the mode is on is decided by
+    /// `close_auction_duration_candles` alone; a start markup of 0 does not disable it.
+    pub close_auction_start_markup: f64,
+    /// Markup the auction decays toward once `candles_since_open` reaches the full duration; may
+    /// be below `close_grid_min_markup` or negative to force de-risking of a stuck position.
+    pub close_auction_end_markup: f64,
+    /// Candles over which the markup decays linearly from start to end. 0 disables the mode.
+    pub close_auction_duration_candles: usize,
+    /// When set, the take-profit grid close and the stop-loss close are managed as a linked
+    /// one-cancels-all pair: `calc_next_close_long/short` return whichever leg is nearer to
+    /// the current market price, and in the backtest only that leg is allowed to fill.
+    pub close_bracket_mode: bool,
+    /// Execution policy applied to resting grid entry orders. See [`ExecutionPolicy`].
+    pub entry_execution_policy: ExecutionPolicy,
+    /// Execution policy applied to resting grid close orders. See [`ExecutionPolicy`].
+    pub close_execution_policy: ExecutionPolicy,
+    /// How a grid entry that would cross the symbol's own resting grid close is handled.
+    pub self_trade_behavior: SelfTradeBehavior,
+    pub entry_grid_double_down_factor: f64,
+    pub entry_grid_spacing_weight: f64,
+    pub entry_grid_spacing_pct: f64,
+    pub entry_initial_ema_dist: f64,
+    /// When non-zero, the initial entry price is blended with a price offset from
+    /// [`StateParams::twap_bands`]'s lower (long) / upper (short) edge by this fraction, instead
+    /// of relying solely on `entry_initial_ema_dist` against the order book. The blend ratio is
+    /// set by `entry_initial_twap_weight`.
+    pub entry_initial_twap_dist: f64,
+    /// Weight of the TWAP-offset price in the
+    /// [`entry_initial_twap_dist`](Self::entry_initial_twap_dist) blend, in `[0, 1]`: 0 keeps the
+    /// order-book-offset price untouched, 1 uses the TWAP-offset price outright, 0.5 is an
+    /// equal-weight average. Has no effect while `entry_initial_twap_dist` is 0.
+    pub entry_initial_twap_weight: f64,
+    pub entry_initial_qty_pct: f64,
+    /// 0 disables volatility scaling of the initial entry; 1 fully scales it against
+    /// [`StateParams::noisiness_now`] vs. [`StateParams::noisiness_median`].
+    pub entry_qty_vol_scaling: f64,
+    /// Window (in candles) used to precompute the per-symbol rolling noisiness series consumed
+    /// by `entry_qty_vol_scaling`.
+    pub entry_qty_vol_window: usize,
+    pub entry_trailing_retracement_pct: f64,
+    pub entry_trailing_grid_ratio: f64,
+    pub entry_trailing_threshold_pct: f64,
+    pub ema_span_0: f64,
+    pub ema_span_1: f64,
+    pub n_positions: usize,
+    pub total_wallet_exposure_limit: f64,
+    pub wallet_exposure_limit: f64,
+    pub unstuck_close_pct: f64,
+    pub unstuck_ema_dist: f64,
+    pub unstuck_loss_allowance_pct: f64,
+    pub unstuck_threshold: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BotParamsPair {
+    pub long: BotParams,
+    pub short: BotParams,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Analysis {
+    pub adg: f64,
+    pub mdg: f64,
+    pub sharpe_ratio: f64,
+    pub drawdown_worst: f64,
+    pub equity_balance_diff_mean: f64,
+    pub equity_balance_diff_max: f64,
+    pub loss_profit_ratio: f64,
+    pub total_funding_paid: f64,
+}